@@ -0,0 +1,163 @@
+use crate::{Options, checksum_file, escape, utils};
+use colored::Colorize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// A relative path under one of the compared roots.
+enum Entry {
+    Missing,
+    /// Couldn't be read, and was reported as an error.
+    Unreadable,
+    Read(Vec<u8>),
+}
+
+/// Classifies a relative path by its entries under each root, the first root being the reference.
+fn status(entries: &[Entry]) -> &'static str {
+    let checksums = entries
+        .iter()
+        .filter_map(|entry| match entry {
+            Entry::Read(checksum) => Some(checksum),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if entries
+        .iter()
+        .any(|entry| matches!(entry, Entry::Unreadable))
+    {
+        "unreadable"
+    } else if matches!(entries[0], Entry::Missing) {
+        "extra"
+    } else if checksums.len() < entries.len() {
+        "missing"
+    } else if checksums.windows(2).all(|pair| pair[0] == pair[1]) {
+        "identical"
+    } else {
+        "differing"
+    }
+}
+
+pub fn do_compare(options: &Options) -> anyhow::Result<()> {
+    if options.files.len() < 2 {
+        anyhow::bail!("--compare needs at least two directories");
+    }
+
+    let mut relative_paths = BTreeSet::new();
+    let mut trees = vec![];
+    for root in &options.files {
+        if !Path::new(root).is_dir() {
+            anyhow::bail!("{root}: not a directory");
+        }
+        let tree = utils::expand_paths(std::slice::from_ref(root))?
+            .into_iter()
+            .map(|file| {
+                Path::new(&file)
+                    .strip_prefix(root)
+                    .expect("walked files are under the root")
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect::<BTreeSet<_>>();
+        relative_paths.extend(tree.iter().cloned());
+        trees.push(tree);
+    }
+
+    let mut anything_failed = false;
+    let mut counts = BTreeMap::<&str, usize>::new();
+    for relative_path in &relative_paths {
+        let entries = options
+            .files
+            .iter()
+            .zip(&trees)
+            .map(|(root, tree)| {
+                if !tree.contains(relative_path) {
+                    return Entry::Missing;
+                }
+                let file = Path::new(root).join(relative_path);
+                let file = file.to_string_lossy();
+                match checksum_file(&file, options.algorithm, options.buffer_size) {
+                    Ok(checksum) => Entry::Read(checksum),
+                    Err(e) => {
+                        eprintln!("{}: {}", file, e.to_string().red());
+                        anything_failed = true;
+                        Entry::Unreadable
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let status = status(&entries);
+        let is_same = status == "identical";
+        *counts.entry(status).or_default() += 1;
+
+        for (root, entry) in options.files.iter().zip(&entries) {
+            let file_head = Path::new(root).join("").to_string_lossy().into_owned();
+            let checksum = match entry {
+                Entry::Read(checksum) => checksum,
+                // Already reported when it was read.
+                Entry::Unreadable => continue,
+                Entry::Missing => {
+                    let file_display = if options.zero {
+                        file_head + relative_path
+                    } else {
+                        escape::escape(&file_head) + &escape::escape(relative_path)
+                    };
+                    eprintln!(
+                        "{}: {}",
+                        file_display,
+                        format!("not found ({status})").red()
+                    );
+                    continue;
+                }
+            };
+            let checksum_display = options.format.encode(options.algorithm, checksum);
+            let colored_checksum = utils::colorize_checksum(&checksum_display, checksum, is_same);
+
+            let escaped = !options.zero
+                && (escape::escape(&file_head) != file_head
+                    || escape::escape(relative_path) != *relative_path);
+            let file_display = if options.zero {
+                file_head + relative_path
+            } else {
+                escape::escape(&file_head).dimmed().to_string() + &escape::escape(relative_path)
+            };
+
+            let line = options.format_line(&colored_checksum, &file_display, escaped);
+            if options.zero {
+                print!("{line}\0");
+            } else {
+                println!("{line}");
+            }
+        }
+    }
+
+    eprintln!(
+        "{}",
+        ["identical", "differing", "missing", "extra", "unreadable"]
+            .iter()
+            .map(|status| format!("{} {status}", counts.get(status).unwrap_or(&0)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    if anything_failed || counts.keys().any(|&status| status != "identical") {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status() {
+        let read = |checksum: &[u8]| Entry::Read(checksum.to_vec());
+        assert_eq!(status(&[read(b"a"), read(b"a")]), "identical");
+        assert_eq!(status(&[read(b"a"), read(b"b")]), "differing");
+        assert_eq!(status(&[read(b"a"), Entry::Missing]), "missing");
+        assert_eq!(status(&[Entry::Missing, read(b"a")]), "extra");
+        assert_eq!(status(&[Entry::Unreadable, read(b"a")]), "unreadable");
+        assert_eq!(status(&[read(b"a"), Entry::Unreadable]), "unreadable");
+    }
+}
@@ -69,3 +69,71 @@ impl HashExt for T {
         Ok(hasher.digest().to_be_bytes().to_vec())
     }
 }
+
+impl HashExt for crc32fast::Hasher {
+    fn hash(data: impl Read, buffer_size: usize) -> Result<Vec<u8>, std::io::Error> {
+        let mut hasher = crc32fast::Hasher::new();
+        let mut buffer = vec![0; buffer_size];
+        let mut reader = data;
+        loop {
+            let bytes_read = reader.read(&mut buffer)?;
+            if bytes_read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..bytes_read]);
+        }
+        Ok(hasher.finalize().to_be_bytes().to_vec())
+    }
+}
+
+/// A hasher fed one buffer at a time, so that several digests can be computed from a single read
+/// of a file.
+pub trait IncrementalHash {
+    fn update_bytes(&mut self, data: &[u8]);
+    fn finish_bytes(self: Box<Self>) -> Vec<u8>;
+}
+
+#[duplicate_item(
+    T;
+    [md5::Md5];
+    [sha1::Sha1];
+    [sha2::Sha224];
+    [sha2::Sha256];
+    [sha2::Sha384];
+    [sha2::Sha512];
+)]
+impl IncrementalHash for T {
+    fn update_bytes(&mut self, data: &[u8]) {
+        Digest::update(self, data);
+    }
+
+    fn finish_bytes(self: Box<Self>) -> Vec<u8> {
+        self.finalize().to_vec()
+    }
+}
+
+#[duplicate_item(
+    T;
+    [xxhash_rust::xxh32::Xxh32];
+    [xxhash_rust::xxh64::Xxh64];
+    [xxhash_rust::xxh3::Xxh3];
+)]
+impl IncrementalHash for T {
+    fn update_bytes(&mut self, data: &[u8]) {
+        self.update(data);
+    }
+
+    fn finish_bytes(self: Box<Self>) -> Vec<u8> {
+        self.digest().to_be_bytes().to_vec()
+    }
+}
+
+impl IncrementalHash for crc32fast::Hasher {
+    fn update_bytes(&mut self, data: &[u8]) {
+        self.update(data);
+    }
+
+    fn finish_bytes(self: Box<Self>) -> Vec<u8> {
+        self.finalize().to_be_bytes().to_vec()
+    }
+}
@@ -0,0 +1,214 @@
+use crate::{Options, checksum_file, checksum_read, escape, utils};
+use anyhow::Context;
+use colored::Colorize;
+use std::collections::HashMap;
+use std::io::{BufRead, Read};
+
+/// Number of bytes hashed from the start of each file before hashing whole files.
+const PARTIAL_SIZE: u64 = 4096;
+
+#[derive(Debug, Copy, Clone, clap::ValueEnum, strum::Display)]
+pub enum LinkKind {
+    #[clap(name = "hard")]
+    #[strum(serialize = "hardlink")]
+    Hard,
+    #[clap(name = "reflink")]
+    #[strum(serialize = "reflink")]
+    Reflink,
+}
+
+pub fn do_duplicates(options: &Options) -> anyhow::Result<()> {
+    let mut anything_failed = false;
+
+    let mut by_size: HashMap<u64, Vec<String>> = HashMap::new();
+    for file in utils::expand_paths(&options.files)? {
+        if file == "-" {
+            eprintln!("{}: {}", file, "stdin is not supported here".red());
+            anything_failed = true;
+            continue;
+        }
+        match fs_err::metadata(&file) {
+            Ok(metadata) if metadata.is_file() => {
+                by_size.entry(metadata.len()).or_default().push(file);
+            }
+            Ok(_) => {
+                eprintln!("{}: {}", file, "not a regular file".yellow());
+            }
+            Err(e) => {
+                eprintln!("{}: {}", file, e.to_string().red());
+                anything_failed = true;
+            }
+        }
+    }
+
+    let mut groups = vec![];
+    for files in by_size.into_values().filter(|files| files.len() > 1) {
+        let partial = bucket_by(files, &mut anything_failed, |file| {
+            let file = fs_err::File::open(file)?;
+            Ok(checksum_read(
+                file.take(PARTIAL_SIZE),
+                options.algorithm,
+                options.buffer_size,
+            )?)
+        });
+        for (_, files) in partial.into_iter().filter(|(_, files)| files.len() > 1) {
+            let full = bucket_by(files, &mut anything_failed, |file| {
+                checksum_file(file, options.algorithm, options.buffer_size)
+            });
+            groups.extend(full.into_iter().filter(|(_, files)| files.len() > 1));
+        }
+    }
+    groups.sort_by(|(_, a), (_, b)| a[0].cmp(&b[0]));
+
+    for (index, (checksum, files)) in groups.iter().enumerate() {
+        if index > 0 {
+            if options.zero {
+                print!("\0");
+            } else {
+                println!();
+            }
+        }
+
+        let checksum_display = options.format.encode(options.algorithm, checksum);
+        let colored_checksum = utils::colorize_checksum(&checksum_display, checksum, false);
+        for file in files {
+            let file_display = if options.zero {
+                file.clone()
+            } else {
+                escape::escape(file)
+            };
+            let line = options.format_line(&colored_checksum, &file_display, file_display != *file);
+            if options.zero {
+                print!("{line}\0");
+            } else {
+                println!("{line}");
+            }
+        }
+
+        let Some(kind) = options.link else {
+            continue;
+        };
+        let (original, duplicates) = files.split_first().expect("groups have 2+ files");
+        for duplicate in duplicates {
+            if !options.apply {
+                eprintln!(
+                    "{}: {} {}",
+                    duplicate,
+                    format!("would be replaced with a {kind} to").yellow(),
+                    original
+                );
+                continue;
+            }
+            match replace_with_link(kind, original, duplicate) {
+                Ok(true) => {
+                    eprintln!(
+                        "{}: {} {}",
+                        duplicate,
+                        format!("replaced with a {kind} to").green(),
+                        original
+                    );
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    eprintln!("{}: {}", duplicate, e.to_string().red());
+                    anything_failed = true;
+                }
+            }
+        }
+    }
+
+    if options.link.is_some() && !options.apply && !groups.is_empty() {
+        eprintln!(
+            "{}",
+            "dry run, nothing was changed. pass --apply to replace duplicates".yellow()
+        );
+    }
+
+    if anything_failed {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Splits `files` into buckets by the key returned from `key`, reporting files that fail.
+fn bucket_by(
+    files: Vec<String>,
+    anything_failed: &mut bool,
+    key: impl Fn(&str) -> anyhow::Result<Vec<u8>>,
+) -> Vec<(Vec<u8>, Vec<String>)> {
+    let mut buckets: HashMap<Vec<u8>, Vec<String>> = HashMap::new();
+    for file in files {
+        match key(&file) {
+            Ok(checksum) => buckets.entry(checksum).or_default().push(file),
+            Err(e) => {
+                eprintln!("{}: {}", file, e.to_string().red());
+                *anything_failed = true;
+            }
+        }
+    }
+
+    buckets.into_iter().collect()
+}
+
+/// Replaces `duplicate` with a link to `original`. Returns `false` if they are already the same
+/// file.
+fn replace_with_link(kind: LinkKind, original: &str, duplicate: &str) -> anyhow::Result<bool> {
+    if is_same_file(original, duplicate)? {
+        return Ok(false);
+    }
+    // Digests like CRC32 or MD5 can collide, so only files that are really equal are replaced.
+    if !same_contents(original, duplicate)? {
+        anyhow::bail!("contents differ from {original} despite the same checksum, skipped");
+    }
+
+    let duplicate_path = std::path::Path::new(duplicate);
+    let file_name = duplicate_path
+        .file_name()
+        .context("invalid file name")?
+        .to_string_lossy();
+    let temp_path = duplicate_path.with_file_name(format!(".{file_name}.ccsum-tmp"));
+    match kind {
+        LinkKind::Hard => fs_err::hard_link(original, &temp_path)?,
+        LinkKind::Reflink => reflink_copy::reflink(original, &temp_path)
+            .with_context(|| format!("failed to reflink {original}"))?,
+    }
+    if let Err(e) = fs_err::rename(&temp_path, duplicate_path) {
+        let _ = fs_err::remove_file(&temp_path);
+        return Err(e.into());
+    }
+
+    Ok(true)
+}
+
+/// Compares the contents of two files byte for byte.
+fn same_contents(a: &str, b: &str) -> anyhow::Result<bool> {
+    let mut a = std::io::BufReader::new(fs_err::File::open(a)?);
+    let mut b = std::io::BufReader::new(fs_err::File::open(b)?);
+    loop {
+        let (chunk_a, chunk_b) = (a.fill_buf()?, b.fill_buf()?);
+        if chunk_a.is_empty() || chunk_b.is_empty() {
+            return Ok(chunk_a.is_empty() && chunk_b.is_empty());
+        }
+        let len = chunk_a.len().min(chunk_b.len());
+        if chunk_a[..len] != chunk_b[..len] {
+            return Ok(false);
+        }
+        a.consume(len);
+        b.consume(len);
+    }
+}
+
+#[cfg(unix)]
+fn is_same_file(a: &str, b: &str) -> anyhow::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let a = fs_err::metadata(a)?;
+    let b = fs_err::metadata(b)?;
+    Ok(a.dev() == b.dev() && a.ino() == b.ino())
+}
+
+#[cfg(not(unix))]
+fn is_same_file(_a: &str, _b: &str) -> anyhow::Result<bool> {
+    Ok(false)
+}
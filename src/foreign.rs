@@ -0,0 +1,213 @@
+//! Output of other tools that spreads entries over several lines or columns: `certutil
+//! -hashfile` blocks, and PowerShell `Get-FileHash` tables and CSV exports.
+
+use crate::{Algorithm, CheckError};
+
+/// A parsed entry: the algorithm, file name and digest, or why the entry is invalid.
+pub type Entry = Result<(Algorithm, String, String), CheckError>;
+
+/// Parses certutil or Get-FileHash output. Returns `None` if `contents` is neither. Entries come
+/// with their line numbers.
+pub fn parse(contents: &str) -> Option<Vec<(usize, Entry)>> {
+    let lines = contents.lines().enumerate().collect::<Vec<_>>();
+    parse_certutil(&lines)
+        .or_else(|| parse_powershell_table(&lines))
+        .or_else(|| parse_powershell_csv(&lines))
+}
+
+fn algorithm(name: &str) -> Result<Algorithm, CheckError> {
+    Algorithm::from_name(name)
+        .ok_or_else(|| CheckError::InvalidLine(format!("unknown algorithm: {name}")))
+}
+
+/// ```text
+/// SHA256 hash of file.txt:
+/// e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855
+/// CertUtil: -hashfile command completed successfully.
+/// ```
+///
+/// Older versions separate the bytes of the digest with spaces. Other lines, except blank lines and
+/// the completion message, are invalid.
+fn parse_certutil(lines: &[(usize, &str)]) -> Option<Vec<(usize, Entry)>> {
+    let mut entries = vec![];
+    let mut any_header = false;
+    let mut lines = lines.iter();
+    while let Some(&(index, line)) = lines.next() {
+        let Some((_, name, filename)) =
+            lazy_regex::regex_captures!(r"^(\S+) hash of (?:file )?(.+):$"i, line.trim_end())
+        else {
+            if !line.trim().is_empty() && !lazy_regex::regex_is_match!(r"^CertUtil: "i, line) {
+                entries.push((
+                    index + 1,
+                    Err(CheckError::InvalidLine("pattern not matched".to_string())),
+                ));
+            }
+            continue;
+        };
+        any_header = true;
+        let digest = lines
+            .next()
+            .map(|(_, line)| line.split_whitespace().collect::<String>())
+            .filter(|digest| !digest.is_empty() && digest.bytes().all(|b| b.is_ascii_hexdigit()));
+        let entry = match digest {
+            Some(digest) => {
+                algorithm(name).map(|algorithm| (algorithm, filename.to_string(), digest))
+            }
+            None => Err(CheckError::InvalidLine(
+                "missing digest after certutil header".to_string(),
+            )),
+        };
+        entries.push((index + 1, entry));
+    }
+
+    any_header.then_some(entries)
+}
+
+/// ```text
+/// Algorithm       Hash                                                                   Path
+/// ---------       ----                                                                   ----
+/// SHA256          E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855       C:\file.txt
+/// ```
+fn parse_powershell_table(lines: &[(usize, &str)]) -> Option<Vec<(usize, Entry)>> {
+    let header = lines
+        .iter()
+        .position(|(_, line)| lazy_regex::regex_is_match!(r"^Algorithm\s+Hash\s+Path\s*$", line))?;
+    let mut rows = lines[header + 1..].iter();
+    let (_, separator) = rows.next()?;
+    if !lazy_regex::regex_is_match!(r"^-+\s+-+\s+-+\s*$", separator) {
+        return None;
+    }
+
+    let entries = rows
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|&(index, line)| {
+            let entry = match lazy_regex::regex_captures!(r"^(\S+)\s+(\S+)\s+(.+?)\s*$", line) {
+                Some((_, name, digest, filename)) => algorithm(name)
+                    .map(|algorithm| (algorithm, filename.to_string(), digest.to_string())),
+                None => Err(CheckError::InvalidLine("pattern not matched".to_string())),
+            };
+            (index + 1, entry)
+        })
+        .collect();
+
+    Some(entries)
+}
+
+/// Splits a CSV line with optionally quoted fields, where quotes are escaped by doubling them.
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+
+    fields
+}
+
+/// ```text
+/// #TYPE Microsoft.PowerShell.Commands.FileHashInfo
+/// "Algorithm","Hash","Path"
+/// "SHA256","E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855","C:\file.txt"
+/// ```
+fn parse_powershell_csv(lines: &[(usize, &str)]) -> Option<Vec<(usize, Entry)>> {
+    let mut rows = lines
+        .iter()
+        .filter(|(_, line)| !line.starts_with('#') && !line.trim().is_empty());
+    let (_, header) = rows.next()?;
+    let header = split_csv(header);
+    let column = |name: &str| header.iter().position(|column| column == name);
+    let (algorithm_column, hash_column, path_column) =
+        (column("Algorithm")?, column("Hash")?, column("Path")?);
+
+    let entries = rows
+        .map(|&(index, line)| {
+            let fields = split_csv(line);
+            let entry = match (
+                fields.get(algorithm_column),
+                fields.get(hash_column),
+                fields.get(path_column),
+            ) {
+                (Some(name), Some(digest), Some(filename)) => {
+                    algorithm(name).map(|algorithm| (algorithm, filename.clone(), digest.clone()))
+                }
+                _ => Err(CheckError::InvalidLine("missing CSV columns".to_string())),
+            };
+            (index + 1, entry)
+        })
+        .collect();
+
+    Some(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_ok(contents: &str) -> Vec<(usize, Algorithm, String, String)> {
+        parse(contents)
+            .unwrap()
+            .into_iter()
+            .map(|(line_number, entry)| {
+                let (algorithm, filename, digest) = entry.unwrap();
+                (line_number, algorithm, filename, digest)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_certutil() {
+        assert_eq!(
+            parse_ok(
+                "SHA256 hash of a b.txt:\nabcd\nCertUtil: -hashfile command completed successfully.\nMD5 hash of file c.txt:\nab cd ef\n"
+            ),
+            [
+                (
+                    1,
+                    Algorithm::SHA256,
+                    "a b.txt".to_string(),
+                    "abcd".to_string()
+                ),
+                (4, Algorithm::MD5, "c.txt".to_string(), "abcdef".to_string()),
+            ]
+        );
+
+        let entries = parse("SHA256 hash of a.txt:\nabcd\nabcd  b.txt\n\n").unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(matches!(entries[1], (3, Err(CheckError::InvalidLine(_)))));
+    }
+
+    #[test]
+    fn test_powershell() {
+        let expected = [(
+            4,
+            Algorithm::SHA256,
+            "C:\\a b.txt".to_string(),
+            "ABCD".to_string(),
+        )];
+        assert_eq!(
+            parse_ok(
+                "\nAlgorithm       Hash      Path\n---------       ----      ----\nSHA256          ABCD      C:\\a b.txt\n\n"
+            ),
+            expected
+        );
+        assert_eq!(
+            parse_ok(
+                "#TYPE Microsoft.PowerShell.Commands.FileHashInfo\n\"Algorithm\",\"Hash\",\"Path\"\n\n\"SHA256\",\"ABCD\",\"C:\\a b.txt\"\n"
+            ),
+            expected
+        );
+        assert_eq!(split_csv(r#""a ""b""",c,"d,e""#), ["a \"b\"", "c", "d,e"]);
+        assert!(parse("abcd  file.txt\n").is_none());
+    }
+}
@@ -0,0 +1,315 @@
+//! hashdeep's manifest format: a `%%%% HASHDEEP-1.0` header, a header naming the columns, and
+//! one `size,hash,...,filename` line per file.
+
+use crate::{Algorithm, CheckError, Options, checksum_file, checksum_file_multi, utils};
+use colored::Colorize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use strum::IntoEnumIterator;
+
+const MAGIC: &str = "%%%% HASHDEEP-1.0";
+
+pub struct Manifest {
+    pub algorithms: Vec<Algorithm>,
+    /// The entries with their line numbers.
+    pub entries: Vec<(usize, Result<Entry, CheckError>)>,
+}
+
+pub struct Entry {
+    pub size: u64,
+    /// Hex digests, in the order of `Manifest::algorithms`.
+    pub digests: Vec<String>,
+    pub path: String,
+}
+
+impl Manifest {
+    /// Parses a hashdeep manifest. Returns `None` if `contents` doesn't start with the hashdeep
+    /// header.
+    pub fn parse(contents: &str) -> Option<anyhow::Result<Manifest>> {
+        let mut lines = contents.lines().enumerate();
+        if lines.next()?.1 != MAGIC {
+            return None;
+        }
+
+        Some(Self::parse_body(lines))
+    }
+
+    fn parse_body<'a>(
+        mut lines: impl Iterator<Item = (usize, &'a str)>,
+    ) -> anyhow::Result<Manifest> {
+        let Some((_, columns)) = lines.next() else {
+            anyhow::bail!("missing hashdeep column header");
+        };
+        let Some(columns) = columns.strip_prefix("%%%% size,") else {
+            anyhow::bail!("invalid hashdeep column header: {columns}");
+        };
+        let mut columns = columns.split(',').collect::<Vec<_>>();
+        if columns.pop() != Some("filename") {
+            anyhow::bail!("hashdeep column header doesn't end with filename");
+        }
+        let algorithms = columns
+            .into_iter()
+            .map(|column| {
+                Algorithm::iter()
+                    .find(|algorithm| algorithm.prefix() == column)
+                    .ok_or_else(|| anyhow::anyhow!("unsupported hashdeep column: {column}"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let entries = lines
+            .filter(|(_, line)| !line.starts_with('#') && !line.trim().is_empty())
+            .map(|(index, line)| (index + 1, parse_entry(line, algorithms.len())))
+            .collect();
+
+        Ok(Manifest {
+            algorithms,
+            entries,
+        })
+    }
+
+    /// The column to verify with: `preferred` if the manifest has it, or the last one otherwise.
+    pub fn column(&self, preferred: Algorithm) -> Option<(usize, Algorithm)> {
+        self.algorithms
+            .iter()
+            .position(|&algorithm| algorithm == preferred)
+            .or_else(|| self.algorithms.len().checked_sub(1))
+            .map(|index| (index, self.algorithms[index]))
+    }
+}
+
+fn parse_entry(line: &str, columns: usize) -> Result<Entry, CheckError> {
+    // File names may contain commas, so only split off the known columns.
+    let mut fields = line.splitn(columns + 2, ',');
+    let size = fields
+        .next()
+        .and_then(|size| size.parse().ok())
+        .ok_or_else(|| CheckError::InvalidLine("invalid hashdeep size".to_string()))?;
+    let digests = fields
+        .by_ref()
+        .take(columns)
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let path = fields
+        .next()
+        .filter(|_| digests.len() == columns)
+        .ok_or_else(|| CheckError::InvalidLine("missing hashdeep columns".to_string()))?;
+
+    Ok(Entry {
+        size,
+        digests,
+        path: path.to_string(),
+    })
+}
+
+/// The columns written: md5 and the selected algorithm, like hashdeep's default of md5 and
+/// sha256.
+fn columns(options: &Options) -> Vec<Algorithm> {
+    let mut algorithms = vec![Algorithm::MD5];
+    if options.algorithm != Algorithm::MD5 {
+        algorithms.push(options.algorithm);
+    }
+    algorithms
+}
+
+/// Prints a hashdeep manifest of the given files and directories.
+pub fn do_hashdeep(options: &Options) -> anyhow::Result<()> {
+    let mut anything_failed = false;
+    let algorithms = columns(options);
+    println!("{MAGIC}");
+    println!(
+        "%%%% size,{},filename",
+        algorithms
+            .iter()
+            .map(|algorithm| algorithm.prefix())
+            .collect::<Vec<_>>()
+            .join(",")
+    );
+    println!("## Invoked from: {}", std::env::current_dir()?.display());
+    println!("## $ {}", std::env::args().collect::<Vec<_>>().join(" "));
+    println!("##");
+
+    for file in utils::expand_paths(&options.files)? {
+        if file == "-" {
+            eprintln!("{}: {}", file, "stdin is not supported here".red());
+            anything_failed = true;
+            continue;
+        }
+        let line = fs_err::metadata(&file)
+            .map_err(anyhow::Error::from)
+            .and_then(|metadata| {
+                let mut fields = vec![metadata.len().to_string()];
+                let checksums = checksum_file_multi(&file, &algorithms, options.buffer_size)?;
+                fields.extend(checksums.iter().map(hex::encode));
+                fields.push(file.clone());
+                Ok(fields.join(","))
+            });
+        match line {
+            Ok(line) => println!("{line}"),
+            Err(e) => {
+                eprintln!("{}: {}", file, e.to_string().red());
+                anything_failed = true;
+            }
+        }
+    }
+
+    if anything_failed {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn normalize(path: &str) -> &str {
+    path.strip_prefix("./").unwrap_or(path)
+}
+
+/// Audits the given files and directories against a hashdeep manifest, classifying each file as
+/// matched, moved, new or changed, and each known file that wasn't found as missing.
+pub fn do_audit(options: &Options, manifest_path: &str) -> anyhow::Result<()> {
+    let contents = fs_err::read_to_string(manifest_path)?;
+    let manifest = Manifest::parse(&contents)
+        .ok_or_else(|| anyhow::anyhow!("{manifest_path}: not a hashdeep manifest"))??;
+    let Some((column, algorithm)) = manifest.column(options.algorithm) else {
+        anyhow::bail!("{manifest_path}: no hashes in the manifest");
+    };
+
+    let mut anything_failed = false;
+    let mut known_by_path = BTreeMap::new();
+    let mut known_by_digest = HashMap::<Vec<u8>, Vec<&str>>::new();
+    for (line_number, entry) in &manifest.entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                eprintln!("{manifest_path}:{line_number}: {}", e.to_string().yellow());
+                anything_failed |= options.strict;
+                continue;
+            }
+        };
+        let Ok(digest) = hex::decode(&entry.digests[column]) else {
+            eprintln!(
+                "{manifest_path}:{line_number}: {}",
+                "invalid hashdeep digest".yellow()
+            );
+            anything_failed |= options.strict;
+            continue;
+        };
+        let path = normalize(&entry.path);
+        known_by_digest
+            .entry(digest.clone())
+            .or_default()
+            .push(path);
+        known_by_path.insert(path, (entry.size, digest));
+    }
+
+    let mut counts = BTreeMap::<&str, usize>::new();
+    let mut seen_paths = HashSet::new();
+    let mut moved_digests = HashSet::new();
+    for file in utils::expand_paths(&options.files)? {
+        if file == "-" {
+            eprintln!("{}: {}", file, "stdin is not supported here".red());
+            anything_failed = true;
+            continue;
+        }
+        let result = fs_err::metadata(&file)
+            .map_err(anyhow::Error::from)
+            .and_then(|metadata| {
+                let digest = checksum_file(&file, algorithm, options.buffer_size)?;
+                Ok((metadata.len(), digest))
+            });
+        let (size, digest) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("{}: {}", file, e.to_string().red());
+                anything_failed = true;
+                continue;
+            }
+        };
+        let path = normalize(&file);
+        seen_paths.insert(path.to_string());
+        let status = match (known_by_path.get(path), known_by_digest.get(&digest)) {
+            (Some((known_size, known_digest)), _)
+                if *known_size == size && *known_digest == digest =>
+            {
+                if !options.quiet {
+                    println!("{}: {}", file, "matched".green());
+                }
+                "matched"
+            }
+            (Some(_), _) => {
+                eprintln!("{}: {}", file, "changed".red());
+                "changed"
+            }
+            (None, Some(known_paths)) => {
+                eprintln!(
+                    "{}: {} {}",
+                    file,
+                    "moved from".yellow(),
+                    known_paths.join(", ")
+                );
+                moved_digests.insert(digest);
+                "moved"
+            }
+            (None, None) => {
+                eprintln!("{}: {}", file, "new".yellow());
+                "new"
+            }
+        };
+        *counts.entry(status).or_default() += 1;
+    }
+
+    for (path, (_, digest)) in &known_by_path {
+        if !seen_paths.contains(*path) && !moved_digests.contains(digest) {
+            eprintln!("{}: {}", path, "missing".red());
+            *counts.entry("missing").or_default() += 1;
+        }
+    }
+
+    eprintln!(
+        "{}",
+        ["matched", "moved", "new", "changed", "missing"]
+            .iter()
+            .map(|status| format!("{} {status}", counts.get(status).unwrap_or(&0)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    if anything_failed || counts.keys().any(|&status| status != "matched") {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let manifest = Manifest::parse(
+            "%%%% HASHDEEP-1.0\n%%%% size,md5,sha256,filename\n## comment\n##\n3,abc,def,dir/a,b.txt\n3,abc\n",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(manifest.algorithms, [Algorithm::MD5, Algorithm::SHA256]);
+        assert_eq!(
+            manifest.column(Algorithm::SHA1),
+            Some((1, Algorithm::SHA256))
+        );
+        assert_eq!(manifest.column(Algorithm::MD5), Some((0, Algorithm::MD5)));
+
+        let (line_number, entry) = &manifest.entries[0];
+        let entry = entry.as_ref().unwrap();
+        assert_eq!(*line_number, 5);
+        assert_eq!(entry.size, 3);
+        assert_eq!(entry.digests, ["abc", "def"]);
+        assert_eq!(entry.path, "dir/a,b.txt");
+        assert!(manifest.entries[1].1.is_err());
+
+        assert!(Manifest::parse("abc  file\n").is_none());
+        assert!(
+            Manifest::parse("%%%% HASHDEEP-1.0\n%%%% size,tiger,filename\n")
+                .unwrap()
+                .is_err()
+        );
+    }
+}
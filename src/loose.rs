@@ -0,0 +1,212 @@
+//! `--loose`: scraping claims like "SHA256 of foo.tar.gz: abcd..." from free-form text such as
+//! release notes, Markdown, HTML or email.
+
+use crate::{Algorithm, ChecksumFormat, Options, output, paths, report};
+use std::collections::HashSet;
+use strum::IntoEnumIterator;
+
+/// Digests shorter than this are too likely to be ordinary words or numbers.
+const MIN_DIGEST_LEN: usize = 20;
+
+/// Characters of digests looked for. Punctuation only used by rarer formats like z85 would make
+/// URLs and paths look like digests.
+fn is_digest_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '=' | '_' | ':' | '-')
+}
+
+/// How many lines above a digest to look for the file it belongs to, as in a heading naming the
+/// file followed by its digests.
+const LOOKBEHIND: usize = 3;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token<'a> {
+    Digest(&'a str),
+    File(&'a str),
+    Algorithm(Algorithm),
+}
+
+/// Splits a line into words, with their offsets, dropping markup and trailing punctuation.
+fn words(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    lazy_regex::regex!(r#"[^\s"'`<>()\[\]{}|*,;!?]+"#)
+        .find_iter(line)
+        .map(|word| {
+            let trimmed = word.as_str().trim_end_matches(['.', ':']);
+            (word.start(), trimmed)
+        })
+        .filter(|(_, word)| !word.is_empty())
+}
+
+/// Algorithms `word` can be a digest for, in any format `parse_checksum_format` accepts.
+fn digest_algorithms(word: &str, format: ChecksumFormat) -> Vec<Algorithm> {
+    if word.len() < MIN_DIGEST_LEN
+        || !word.chars().all(is_digest_char)
+        || !word.bytes().any(|b| b.is_ascii_digit())
+    {
+        return vec![];
+    }
+    let candidates = match Algorithm::from_digest(word) {
+        Some(algorithm) => vec![algorithm],
+        None => Algorithm::iter().collect(),
+    };
+    candidates
+        .into_iter()
+        .filter(|&algorithm| crate::parse_checksum_format(word, algorithm, format).is_ok())
+        .collect()
+}
+
+fn classify<'a>(options: &Options, manifest: &str, word: &'a str) -> Option<Token<'a>> {
+    if let Some(algorithm) = Algorithm::from_name(word.strip_suffix("sum").unwrap_or(word)) {
+        return Some(Token::Algorithm(algorithm));
+    }
+    if !digest_algorithms(word, options.format).is_empty() {
+        return Some(Token::Digest(word));
+    }
+    let (path, _) = paths::resolve(options, manifest, word);
+    std::path::Path::new(&path)
+        .is_file()
+        .then_some(Token::File(word))
+}
+
+/// The file a digest at `offset` on line `index` most likely belongs to: the nearest file name on
+/// the same line, then on the lines above, then on the line below.
+fn nearest_file<'a>(
+    lines: &[Vec<(usize, Token<'a>)>],
+    index: usize,
+    offset: usize,
+) -> Option<&'a str> {
+    let files = |index: usize| {
+        lines[index]
+            .iter()
+            .filter_map(|(offset, token)| match token {
+                Token::File(file) => Some((*offset, *file)),
+                _ => None,
+            })
+    };
+    files(index)
+        .min_by_key(|(file_offset, _)| file_offset.abs_diff(offset))
+        .or_else(|| {
+            (index.saturating_sub(LOOKBEHIND)..index)
+                .rev()
+                .find_map(|index| files(index).next_back())
+        })
+        .or_else(|| lines.get(index + 1).and_then(|_| files(index + 1).next()))
+        .map(|(_, file)| file)
+}
+
+/// Algorithms named on the given lines, like `SHA256` or `sha512sum`.
+fn named_algorithms(lines: &[Vec<(usize, Token<'_>)>]) -> Vec<Algorithm> {
+    lines
+        .iter()
+        .flatten()
+        .filter_map(|(_, token)| match token {
+            Token::Algorithm(algorithm) => Some(*algorithm),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Verifies every digest in `contents` that has the name of an existing file near it.
+pub fn do_loose(
+    options: &Options,
+    writer: &mut output::RecordWriter,
+    reporter: &mut report::Reporter,
+    file: &str,
+    contents: &str,
+) -> anyhow::Result<Vec<Option<bool>>> {
+    let lines = contents
+        .lines()
+        .map(|line| {
+            words(line)
+                .filter_map(|(offset, word)| Some((offset, classify(options, file, word)?)))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut seen = HashSet::new();
+    let mut results = vec![];
+    for (index, tokens) in lines.iter().enumerate() {
+        for &(offset, ref token) in tokens {
+            let Token::Digest(digest) = *token else {
+                continue;
+            };
+            let Some(filename) = nearest_file(&lines, index, offset) else {
+                continue;
+            };
+            if !seen.insert((filename, digest)) {
+                continue;
+            }
+
+            // A digest can be valid for several algorithms in different formats, e.g. 64 hex
+            // characters are also 48 bytes of base64. Prefer what the text says.
+            let mut candidates = digest_algorithms(digest, options.format);
+            let named = named_algorithms(&lines[index.saturating_sub(LOOKBEHIND)..=index]);
+            if candidates.iter().any(|algorithm| named.contains(algorithm)) {
+                candidates.retain(|algorithm| named.contains(algorithm));
+            }
+            candidates.sort_by_key(|&algorithm| algorithm != options.algorithm);
+            let (path, _) = paths::resolve(options, file, filename);
+            let algorithm = candidates
+                .iter()
+                .copied()
+                .find(|&algorithm| {
+                    candidates.len() == 1
+                        || crate::process_line(
+                            algorithm,
+                            options.buffer_size,
+                            options.format,
+                            &path,
+                            digest,
+                        )
+                        .is_ok()
+                })
+                .unwrap_or(candidates[0]);
+
+            reporter.start_line(file, index + 1);
+            results.push(crate::do_entry(
+                options, writer, reporter, file, algorithm, filename, digest,
+            )?);
+        }
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_words() {
+        assert_eq!(
+            words("SHA256 of `foo.tar.gz`: <code>abc</code>.").collect::<Vec<_>>(),
+            [
+                (0, "SHA256"),
+                (7, "of"),
+                (11, "foo.tar.gz"),
+                (25, "code"),
+                (30, "abc"),
+                (34, "/code"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_digest_algorithms() {
+        let sha256 = "1894a19c85ba153acbf743ac4e43fc004c891604b26f8c69e1e83ea2afc7c48f";
+        assert_eq!(
+            digest_algorithms(sha256, ChecksumFormat::Hex),
+            [Algorithm::SHA256, Algorithm::SHA384]
+        );
+        assert_eq!(
+            digest_algorithms(&format!("sha256:{sha256}"), ChecksumFormat::Hex),
+            [Algorithm::SHA256]
+        );
+        assert!(digest_algorithms("documentation", ChecksumFormat::Hex).is_empty());
+        assert!(digest_algorithms("b739e0d5", ChecksumFormat::Hex).is_empty());
+        // Valid z85 for SHA224.
+        assert!(
+            digest_algorithms("https://example.com/demo/foo/01.txt", ChecksumFormat::Hex)
+                .is_empty()
+        );
+    }
+}
@@ -1,16 +1,40 @@
 use crate::digest_ext::HashExt;
 use base64::Engine;
-use clap::{CommandFactory, Parser};
+use clap::{CommandFactory, FromArgMatches, Parser};
 use colored::Colorize;
-use std::io::{BufRead, Read};
+use std::io::{BufRead, IsTerminal, Read, Write};
 use strum::IntoEnumIterator;
+mod compare;
 mod digest_ext;
+mod duplicates;
 mod escape;
+mod foreign;
+mod hashdeep;
+mod loose;
+mod mtree;
+mod multiformats;
+mod nix32;
+mod output;
+mod paths;
+mod pgp;
+mod report;
+mod sidecar;
+mod signify;
+mod sshsig;
 mod table;
+mod template;
 mod utils;
 
 #[derive(
-    Debug, Copy, Clone, clap::ValueEnum, strum::Display, strum::EnumString, strum::EnumIter,
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    clap::ValueEnum,
+    strum::Display,
+    strum::EnumString,
+    strum::EnumIter,
 )]
 enum Algorithm {
     #[clap(name = "md5")]
@@ -31,6 +55,8 @@ enum Algorithm {
     Xxh64,
     #[clap(name = "xxh3")]
     Xxh3,
+    #[clap(name = "crc32")]
+    Crc32,
 }
 
 impl Algorithm {
@@ -44,9 +70,59 @@ impl Algorithm {
             Algorithm::SHA512 => 64,
             Algorithm::Xxh32 => 4,
             Algorithm::Xxh64 => 8,
-            Algorithm::Xxh3 => 16, // Xxh3 can be configured, but we use the default
+            Algorithm::Xxh3 => 8, // Xxh3 can be configured, but we use the default 64-bit hash
+            Algorithm::Crc32 => 4,
+        }
+    }
+
+    /// The name used in prefixed digests like `sha256:...`.
+    fn prefix(self) -> &'static str {
+        match self {
+            Algorithm::MD5 => "md5",
+            Algorithm::SHA1 => "sha1",
+            Algorithm::SHA224 => "sha224",
+            Algorithm::SHA256 => "sha256",
+            Algorithm::SHA384 => "sha384",
+            Algorithm::SHA512 => "sha512",
+            Algorithm::Xxh32 => "xxh32",
+            Algorithm::Xxh64 => "xxh64",
+            Algorithm::Xxh3 => "xxh3",
+            Algorithm::Crc32 => "crc32",
         }
     }
+
+    /// Returns the algorithm named by the prefix of a digest like `sha256:...` or `sha256-...`.
+    fn from_prefixed(digest: &str) -> Option<Algorithm> {
+        let (prefix, _) = digest.split_once([':', '-'])?;
+        Algorithm::iter().find(|algorithm| algorithm.prefix() == prefix)
+    }
+
+    /// Returns the algorithm named by another tool, like `SHA2-256` (openssl), `SHA256`
+    /// (certutil) or `sha-512`.
+    fn from_name(name: &str) -> Option<Algorithm> {
+        let name = name.to_lowercase().replace(['-', '_'], "");
+        let name = match name.as_str() {
+            "sha2224" => "sha224",
+            "sha2256" => "sha256",
+            "sha2384" => "sha384",
+            "sha2512" => "sha512",
+            "xxhash32" => "xxh32",
+            "xxhash64" => "xxh64",
+            "xxhash3" | "xxh364" => "xxh3",
+            name => name,
+        };
+        Algorithm::iter().find(|algorithm| algorithm.prefix() == name)
+    }
+
+    /// Returns the algorithm a digest describes by itself, with a prefix, or as a multihash or
+    /// CID.
+    fn from_digest(digest: &str) -> Option<Algorithm> {
+        Algorithm::from_prefixed(digest).or_else(|| {
+            multiformats::decode_multihash(digest)
+                .or_else(|| multiformats::decode_cid(digest))
+                .map(|(algorithm, _)| algorithm)
+        })
+    }
 }
 
 #[derive(
@@ -69,10 +145,34 @@ enum ChecksumFormat {
     Base64NoPad,
     #[clap(name = "base64-url", alias = "base64url")]
     Base64Url,
+    #[clap(name = "hex-upper", alias = "hexupper")]
+    HexUpper,
+    #[clap(name = "base32", alias = "base32-pad")]
+    Base32,
+    #[clap(name = "base32-no-pad", alias = "base32n")]
+    Base32NoPad,
+    #[clap(name = "base32-crockford", alias = "crockford")]
+    Base32Crockford,
+    #[clap(name = "base58", alias = "base58btc")]
+    Base58,
+    #[clap(name = "z85")]
+    Z85,
+    #[clap(name = "oci", alias = "oci-digest")]
+    Oci,
+    #[clap(name = "sri")]
+    Sri,
+    #[clap(name = "nix32", alias = "nix")]
+    Nix32,
+    #[clap(name = "multihash")]
+    Multihash,
+    #[clap(name = "cid")]
+    Cid,
+    #[clap(name = "raw")]
+    Raw,
 }
 
 impl ChecksumFormat {
-    fn encode(self, checksum: &[u8]) -> String {
+    fn encode(self, algorithm: Algorithm, checksum: &[u8]) -> String {
         match self {
             ChecksumFormat::Hex => hex::encode(checksum),
             ChecksumFormat::Base64 => base64::engine::general_purpose::STANDARD.encode(checksum),
@@ -82,10 +182,32 @@ impl ChecksumFormat {
             ChecksumFormat::Base64Url => {
                 base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(checksum)
             }
+            ChecksumFormat::HexUpper => hex::encode_upper(checksum),
+            ChecksumFormat::Base32 => {
+                base32::encode(base32::Alphabet::Rfc4648 { padding: true }, checksum)
+            }
+            ChecksumFormat::Base32NoPad => {
+                base32::encode(base32::Alphabet::Rfc4648 { padding: false }, checksum)
+            }
+            ChecksumFormat::Base32Crockford => {
+                base32::encode(base32::Alphabet::Crockford, checksum)
+            }
+            ChecksumFormat::Base58 => bs58::encode(checksum).into_string(),
+            ChecksumFormat::Z85 => z85::encode(checksum),
+            ChecksumFormat::Oci => format!("{}:{}", algorithm.prefix(), hex::encode(checksum)),
+            ChecksumFormat::Sri => format!(
+                "{}-{}",
+                algorithm.prefix(),
+                base64::engine::general_purpose::STANDARD.encode(checksum)
+            ),
+            ChecksumFormat::Nix32 => format!("{}:{}", algorithm.prefix(), nix32::encode(checksum)),
+            ChecksumFormat::Multihash => multiformats::encode_multihash(algorithm, checksum),
+            ChecksumFormat::Cid => multiformats::encode_cid(algorithm, checksum),
+            ChecksumFormat::Raw => unreachable!("raw digests are written as bytes"),
         }
     }
 
-    fn decode(self, source: &str) -> Result<Vec<u8>, CheckError> {
+    fn decode(self, algorithm: Algorithm, source: &str) -> Result<Vec<u8>, CheckError> {
         match self {
             ChecksumFormat::Hex => hex::decode(source)
                 .map_err(|_| CheckError::InvalidLine("invalid hex format".to_string())),
@@ -98,12 +220,102 @@ impl ChecksumFormat {
             ChecksumFormat::Base64Url => base64::engine::general_purpose::URL_SAFE_NO_PAD
                 .decode(source)
                 .map_err(|_| CheckError::InvalidLine("invalid base64 url format".to_string())),
+            ChecksumFormat::HexUpper => {
+                if source.chars().any(|c| c.is_ascii_lowercase()) {
+                    return Err(CheckError::InvalidLine(
+                        "invalid uppercase hex format".to_string(),
+                    ));
+                }
+                hex::decode(source).map_err(|_| {
+                    CheckError::InvalidLine("invalid uppercase hex format".to_string())
+                })
+            }
+            ChecksumFormat::Base32 => {
+                if source.len() % 8 != 0 {
+                    return Err(CheckError::InvalidLine("invalid base32 format".to_string()));
+                }
+                base32::decode(base32::Alphabet::Rfc4648 { padding: true }, source)
+                    .ok_or_else(|| CheckError::InvalidLine("invalid base32 format".to_string()))
+            }
+            ChecksumFormat::Base32NoPad => {
+                base32::decode(base32::Alphabet::Rfc4648 { padding: false }, source).ok_or_else(
+                    || CheckError::InvalidLine("invalid base32 no pad format".to_string()),
+                )
+            }
+            ChecksumFormat::Base32Crockford => base32::decode(base32::Alphabet::Crockford, source)
+                .ok_or_else(|| {
+                    CheckError::InvalidLine("invalid crockford base32 format".to_string())
+                }),
+            ChecksumFormat::Base58 => bs58::decode(source)
+                .into_vec()
+                .map_err(|_| CheckError::InvalidLine("invalid base58 format".to_string())),
+            ChecksumFormat::Z85 => z85::decode(source)
+                .map_err(|_| CheckError::InvalidLine("invalid z85 format".to_string())),
+            ChecksumFormat::Oci => source
+                .strip_prefix(&format!("{}:", algorithm.prefix()))
+                .and_then(|digest| hex::decode(digest).ok())
+                .ok_or_else(|| CheckError::InvalidLine("invalid oci digest format".to_string())),
+            ChecksumFormat::Sri => source
+                .strip_prefix(&format!("{}-", algorithm.prefix()))
+                .and_then(|digest| {
+                    base64::engine::general_purpose::STANDARD
+                        .decode(digest)
+                        .ok()
+                })
+                .ok_or_else(|| CheckError::InvalidLine("invalid sri format".to_string())),
+            ChecksumFormat::Nix32 => source
+                .strip_prefix(&format!("{}:", algorithm.prefix()))
+                .and_then(|digest| nix32::decode(digest).ok())
+                .ok_or_else(|| CheckError::InvalidLine("invalid nix32 format".to_string())),
+            ChecksumFormat::Multihash => multiformats::decode_multihash(source)
+                .filter(|(decoded, _)| *decoded == algorithm)
+                .map(|(_, digest)| digest)
+                .ok_or_else(|| CheckError::InvalidLine("invalid multihash format".to_string())),
+            ChecksumFormat::Cid => multiformats::decode_cid(source)
+                .filter(|(decoded, _)| *decoded == algorithm)
+                .map(|(_, digest)| digest)
+                .ok_or_else(|| CheckError::InvalidLine("invalid cid format".to_string())),
+            ChecksumFormat::Raw => Err(CheckError::InvalidLine(
+                "raw digests cannot be read from a checksum file".to_string(),
+            )),
         }
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, clap::ValueEnum, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+enum FormatStyle {
+    #[clap(name = "gnu")]
+    Gnu,
+    #[clap(name = "bsd")]
+    Bsd,
+    #[clap(name = "sfv")]
+    Sfv,
+    #[clap(name = "hashdeep")]
+    Hashdeep,
+    #[clap(name = "mtree")]
+    Mtree,
+}
+
+impl FormatStyle {
+    /// Whether the style is a whole manifest rather than one line per checksum.
+    fn is_manifest(self) -> bool {
+        matches!(self, FormatStyle::Hashdeep | FormatStyle::Mtree)
+    }
+}
+
 #[derive(Debug, Parser)]
 #[clap(version, about)]
+#[clap(group(
+    clap::ArgGroup::new("check_mode")
+        .multiple(true)
+        .args(["check", "check_sidecars"])
+))]
+#[clap(group(
+    clap::ArgGroup::new("group_mode")
+        .multiple(true)
+        .args(["group", "group_with_check", "group_by_regex", "group_by_basename_stem"])
+))]
 struct Options {
     /// print shell completion script.
     #[clap(long)]
@@ -125,6 +337,19 @@ struct Options {
     #[clap(long, default_value = "false", help_heading = "Display options")]
     tag: bool,
 
+    /// the style of checksum lines. `bsd` is the same as --tag, `sfv` always uses CRC32, and
+    /// `hashdeep` writes a hashdeep manifest with the size, MD5 and the selected algorithm. `mtree`
+    /// writes a BSD mtree specification with the type, mode, owner, size and link target.
+    #[clap(
+        long,
+        value_name = "STYLE",
+        default_value = "gnu",
+        help_heading = "Display options",
+        value_enum,
+        conflicts_with = "tag"
+    )]
+    format_style: FormatStyle,
+
     /// end each output line with a NULL character instead of newline, and disable file name
     /// escaping.
     #[clap(short, long, default_value = "false", help_heading = "Display options")]
@@ -144,6 +369,138 @@ struct Options {
     #[clap(long, default_value = "false", help_heading = "Check mode options")]
     ignore_missing: bool,
 
+    /// resolve relative file names in a manifest against the directory containing it, instead of
+    /// the current directory.
+    #[clap(
+        long,
+        default_value = "false",
+        help_heading = "Check mode options",
+        requires = "check"
+    )]
+    relative_to_manifest: bool,
+
+    /// resolve relative file names in manifests against DIR, instead of the current directory.
+    #[clap(
+        long,
+        value_name = "DIR",
+        help_heading = "Check mode options",
+        requires = "check",
+        conflicts_with = "relative_to_manifest"
+    )]
+    directory: Option<String>,
+
+    /// remove PREFIX from file names in manifests, e.g. `/build/out/`. can be given more than
+    /// once, and the first matching prefix is used.
+    #[clap(
+        long,
+        value_name = "PREFIX",
+        help_heading = "Check mode options",
+        requires = "check"
+    )]
+    strip_prefix: Vec<String>,
+
+    /// replace the prefix OLD of file names in manifests with NEW. can be given more than once.
+    #[clap(
+        long,
+        value_name = "OLD=NEW",
+        help_heading = "Check mode options",
+        requires = "check"
+    )]
+    replace_prefix: Vec<paths::PrefixReplacement>,
+
+    /// convert path separators in manifests to the ones of this platform, e.g. `dir\file` to
+    /// `dir/file`. prefixes given to --strip-prefix and --replace-prefix are converted too.
+    #[clap(
+        long,
+        default_value = "false",
+        help_heading = "Check mode options",
+        requires = "check"
+    )]
+    convert_separators: bool,
+
+    /// match file names in manifests and their prefixes case-insensitively.
+    #[clap(
+        long,
+        default_value = "false",
+        help_heading = "Check mode options",
+        requires = "check"
+    )]
+    ignore_case: bool,
+
+    /// when a file in a manifest is not found, retry with its name in Unicode NFC or NFD, or
+    /// both with `auto`. useful for manifests from macOS, which stores names in NFD.
+    #[clap(
+        long,
+        value_name = "FORM",
+        help_heading = "Check mode options",
+        requires = "check",
+        value_enum
+    )]
+    normalize_names: Option<paths::Normalization>,
+
+    /// scan free-form text, like release notes or email, for digests near names of existing
+    /// files, and verify those claims.
+    #[clap(
+        long,
+        default_value = "false",
+        help_heading = "Check mode options",
+        requires = "check"
+    )]
+    loose: bool,
+
+    /// write the signature block of a PGP cleartext signed manifest to PATH. with the body from
+    /// --extract-signed-body, `gpg --verify` can check it as a detached signature. with several
+    /// signed manifests, the last one is written.
+    #[clap(
+        long,
+        value_name = "PATH",
+        help_heading = "Check mode options",
+        requires = "check"
+    )]
+    extract_signature: Option<String>,
+
+    /// write the signed body of a PGP cleartext signed manifest to PATH, with dash-escaping
+    /// removed.
+    #[clap(
+        long,
+        value_name = "PATH",
+        help_heading = "Check mode options",
+        requires = "check"
+    )]
+    extract_signed_body: Option<String>,
+
+    /// before checking a manifest, verify its signify or minisign signature (`FILE.sig` or
+    /// `FILE.minisig`) with the Ed25519 public key in KEY. no entry is checked if it's invalid.
+    #[clap(
+        long,
+        value_name = "KEY",
+        help_heading = "Check mode options",
+        requires = "check",
+        conflicts_with = "allowed_signers"
+    )]
+    pubkey: Option<String>,
+
+    /// before checking a manifest, verify its SSH signature (`FILE.sig`, as written by
+    /// `ssh-keygen -Y sign`) for --namespace and --identity against the keys in an allowed_signers
+    /// FILE. no entry is checked if it's invalid.
+    #[clap(
+        long,
+        value_name = "FILE",
+        help_heading = "Check mode options",
+        requires_all = ["check", "identity"]
+    )]
+    allowed_signers: Option<String>,
+
+    /// the principal the SSH signature must be from, like `ssh-keygen -Y verify -I`. the key must
+    /// be allowed for PRINCIPAL in --allowed-signers.
+    #[clap(
+        long,
+        value_name = "PRINCIPAL",
+        help_heading = "Check mode options",
+        requires = "allowed_signers"
+    )]
+    identity: Option<String>,
+
     /// don't put OK for each successfully verified file.
     #[clap(long, default_value = "false", help_heading = "Check mode options")]
     quiet: bool,
@@ -156,6 +513,16 @@ struct Options {
     #[clap(long, default_value = "false", help_heading = "Check mode options")]
     strict: bool,
 
+    /// write a report with one test case per checksum line. KIND is `junit` or `tap`, and the
+    /// report is written to PATH, or to stdout instead of the OK lines if PATH is omitted.
+    #[clap(
+        long,
+        value_name = "KIND[=PATH]",
+        help_heading = "Check mode options",
+        requires = "check_mode"
+    )]
+    report: Vec<report::ReportTarget>,
+
     /// warn about improperly formatted checksum lines.
     #[clap(
         short,
@@ -199,6 +566,158 @@ struct Options {
     )]
     group_with_check: Option<u64>,
 
+    /// with --group-with-check, only fail groups where fewer than N copies agree on the
+    /// majority checksum, instead of failing on any difference.
+    #[clap(
+        long,
+        value_name = "N",
+        help_heading = "Group mode options",
+        requires = "group_with_check",
+        value_parser = clap::value_parser!(u64).range(1..),
+    )]
+    require_quorum: Option<u64>,
+
+    /// group output by the capture groups of REGEX matched against the path, instead of by the
+    /// last segments. files that don't match are skipped.
+    #[clap(
+        long,
+        value_name = "REGEX",
+        help_heading = "Group mode options",
+        value_parser = regex::Regex::new,
+        conflicts_with = "group_by_basename_stem"
+    )]
+    group_by_regex: Option<regex::Regex>,
+
+    /// group output by the file name without its extension, instead of by the last segments.
+    #[clap(long, default_value = "false", help_heading = "Group mode options")]
+    group_by_basename_stem: bool,
+
+    /// read the files as checksum manifests, and group the entries listed in them instead of
+    /// reading the listed files. the manifest of each entry is shown when stdout is a terminal.
+    #[clap(
+        long,
+        default_value = "false",
+        help_heading = "Group mode options",
+        requires = "group_mode"
+    )]
+    from_manifests: bool,
+
+    /// find files with identical contents and print them in groups. directories are searched
+    /// recursively.
+    #[clap(
+        short = 'D',
+        long,
+        default_value = "false",
+        help_heading = "Duplicate mode options",
+        conflicts_with_all = ["check", "group", "group_with_check"]
+    )]
+    duplicates: bool,
+
+    /// compare two or more directories by the paths relative to each of them, and fail if any
+    /// file differs or exists only in some of them.
+    #[clap(
+        long,
+        default_value = "false",
+        help_heading = "Compare mode options",
+        conflicts_with_all = ["check", "group", "group_with_check", "duplicates"]
+    )]
+    compare: bool,
+
+    /// replace duplicates with links to the first file of each group.
+    #[clap(
+        long,
+        help_heading = "Duplicate mode options",
+        requires = "duplicates",
+        value_enum
+    )]
+    link: Option<duplicates::LinkKind>,
+
+    /// actually replace duplicates. without this, --link only prints what it would do. files are
+    /// compared byte for byte first, so a digest collision never replaces a file.
+    #[clap(
+        long,
+        default_value = "false",
+        help_heading = "Duplicate mode options",
+        requires = "link"
+    )]
+    apply: bool,
+
+    /// write a checksum file next to each input, named after the algorithm (e.g. `file.sha256`).
+    /// STYLE is `gnu` for a sha256sum-style line, or `bare` for the digest only.
+    #[clap(
+        long,
+        value_name = "STYLE",
+        help_heading = "Sidecar options",
+        num_args = 0..=1,
+        default_missing_value = "gnu",
+        require_equals = true,
+        value_enum,
+        conflicts_with_all = ["check", "group_mode", "duplicates", "compare", "printf", "output_format"]
+    )]
+    sidecar: Option<sidecar::SidecarStyle>,
+
+    /// find checksum files like `file.sha256` or `file.md5` in the given directories, and verify
+    /// the files next to them with the algorithm named by the extension.
+    #[clap(
+        long,
+        default_value = "false",
+        help_heading = "Sidecar options",
+        conflicts_with_all = ["check", "group_mode", "duplicates", "compare", "sidecar"]
+    )]
+    check_sidecars: bool,
+
+    /// sign the given manifests with the unencrypted signify, minisign or OpenSSH secret key in
+    /// KEY, writing `FILE.sig` or `FILE.minisig` next to each. SSH signatures are made for
+    /// --namespace.
+    #[clap(
+        long,
+        value_name = "KEY",
+        help_heading = "Signing options",
+        conflicts_with_all = ["check", "check_sidecars", "sidecar", "group_mode", "duplicates", "compare", "printf", "output_format"]
+    )]
+    sign: Option<String>,
+
+    /// the namespace of SSH signatures made with --sign, and verified with -c --allowed-signers.
+    /// it keeps signatures made for one purpose from being valid for another.
+    #[clap(
+        long,
+        value_name = "NAMESPACE",
+        default_value = "file",
+        help_heading = "Signing options"
+    )]
+    namespace: String,
+
+    /// audit the given files and directories against a hashdeep manifest, reporting files as
+    /// matched, moved, new, changed or missing.
+    #[clap(
+        long,
+        value_name = "MANIFEST",
+        help_heading = "Audit options",
+        conflicts_with_all = ["check", "check_sidecars", "sidecar", "group_mode", "duplicates", "compare", "printf", "output_format"]
+    )]
+    audit: Option<String>,
+
+    /// print results as plain text, a JSON array, or one JSON object per line.
+    #[clap(
+        long,
+        default_value = "text",
+        help_heading = "Display options",
+        value_enum,
+        conflicts_with_all = ["zero", "duplicates", "compare"]
+    )]
+    output_format: output::OutputFormat,
+
+    /// print each checksum with a template instead of the GNU or BSD line. placeholders: %h
+    /// digest, %f path, %e escaped path, %s size, %t mtime, %a algorithm, %H/%T path head/tail in
+    /// group mode, %% percent sign. escapes: \n, \t, \0, \\.
+    #[clap(
+        long,
+        value_name = "TEMPLATE",
+        help_heading = "Display options",
+        conflicts_with_all = ["tag", "format_style", "zero", "output_format", "check", "duplicates", "compare"]
+    )]
+    printf: Option<template::Template>,
+
     /// colorize the output, even if stdout is not a tty.
     #[clap(
         alias = "C",
@@ -216,6 +735,59 @@ struct Options {
     files: Vec<String>,
 }
 
+impl Options {
+    /// The directory relative file names in `manifest` are resolved against, if not the current
+    /// directory.
+    fn base_directory(&self, manifest: &str) -> Option<std::path::PathBuf> {
+        if let Some(directory) = &self.directory {
+            Some(directory.into())
+        } else if self.relative_to_manifest && manifest != "-" {
+            std::path::Path::new(manifest).parent().map(Into::into)
+        } else {
+            None
+        }
+    }
+
+    /// Whether a report is written to stdout, which then takes the place of the OK lines.
+    fn report_to_stdout(&self) -> bool {
+        self.report.iter().any(|target| target.path.is_none())
+    }
+
+    fn style(&self) -> FormatStyle {
+        if self.tag {
+            FormatStyle::Bsd
+        } else {
+            self.format_style
+        }
+    }
+
+    /// Formats a checksum line. Like coreutils, GNU and BSD lines start with a backslash when the
+    /// file name is escaped, so that only those are unescaped when checked.
+    fn format_line(&self, checksum: &str, file: &str, escaped: bool) -> String {
+        let marker = if escaped { "\\" } else { "" };
+        match self.style() {
+            FormatStyle::Gnu => format!("{marker}{checksum}  {file}"),
+            FormatStyle::Bsd => format!("{marker}{} ({}) = {}", self.algorithm, file, checksum),
+            FormatStyle::Sfv => format!("{file} {checksum}"),
+            FormatStyle::Hashdeep | FormatStyle::Mtree => {
+                unreachable!("manifest styles are written separately")
+            }
+        }
+    }
+
+    fn group_key(&self) -> Option<utils::GroupKey<'_>> {
+        if let Some(regex) = &self.group_by_regex {
+            Some(utils::GroupKey::Regex(regex))
+        } else if self.group_by_basename_stem {
+            Some(utils::GroupKey::BasenameStem)
+        } else {
+            self.group
+                .or(self.group_with_check)
+                .map(|n| utils::GroupKey::LastSegments(n as usize))
+        }
+    }
+}
+
 fn checksum_read(
     data: impl Read,
     algorithm: Algorithm,
@@ -231,9 +803,53 @@ fn checksum_read(
         Algorithm::Xxh32 => xxhash_rust::xxh32::Xxh32::hash(data, buffer_size),
         Algorithm::Xxh64 => xxhash_rust::xxh64::Xxh64::hash(data, buffer_size),
         Algorithm::Xxh3 => xxhash_rust::xxh3::Xxh3::hash(data, buffer_size),
+        Algorithm::Crc32 => crc32fast::Hasher::hash(data, buffer_size),
     }
 }
 
+fn incremental_hasher(algorithm: Algorithm) -> Box<dyn digest_ext::IncrementalHash> {
+    match algorithm {
+        Algorithm::MD5 => Box::new(md5::Md5::default()),
+        Algorithm::SHA1 => Box::new(sha1::Sha1::default()),
+        Algorithm::SHA224 => Box::new(sha2::Sha224::default()),
+        Algorithm::SHA256 => Box::new(sha2::Sha256::default()),
+        Algorithm::SHA384 => Box::new(sha2::Sha384::default()),
+        Algorithm::SHA512 => Box::new(sha2::Sha512::default()),
+        Algorithm::Xxh32 => Box::new(xxhash_rust::xxh32::Xxh32::new(0)),
+        Algorithm::Xxh64 => Box::new(xxhash_rust::xxh64::Xxh64::new(0)),
+        Algorithm::Xxh3 => Box::new(xxhash_rust::xxh3::Xxh3::new()),
+        Algorithm::Crc32 => Box::new(crc32fast::Hasher::new()),
+    }
+}
+
+/// Computes the checksums of `file` for each of `algorithms`, reading it only once.
+fn checksum_file_multi(
+    file: &str,
+    algorithms: &[Algorithm],
+    buffer_size: usize,
+) -> anyhow::Result<Vec<Vec<u8>>> {
+    let mut hashers = algorithms
+        .iter()
+        .map(|&algorithm| incremental_hasher(algorithm))
+        .collect::<Vec<_>>();
+    let mut file = fs_err::File::open(file)?;
+    let mut buffer = vec![0; buffer_size];
+    loop {
+        let bytes_read = file.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        for hasher in &mut hashers {
+            hasher.update_bytes(&buffer[..bytes_read]);
+        }
+    }
+
+    Ok(hashers
+        .into_iter()
+        .map(|hasher| hasher.finish_bytes())
+        .collect())
+}
+
 fn checksum_file(file: &str, algorithm: Algorithm, buffer_size: usize) -> anyhow::Result<Vec<u8>> {
     let file = fs_err::File::open(file)?;
     Ok(checksum_read(&file, algorithm, buffer_size)?)
@@ -244,7 +860,8 @@ fn checksum_stdin(algorithm: Algorithm, buffer_size: usize) -> anyhow::Result<Ve
 }
 
 fn main() -> anyhow::Result<()> {
-    let mut options = Options::parse_from(wild::args());
+    let matches = Options::command().get_matches_from(wild::args());
+    let mut options = Options::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
 
     if let Some(shell) = options.completion {
         clap_complete::generate(
@@ -256,7 +873,7 @@ fn main() -> anyhow::Result<()> {
         return Ok(());
     }
 
-    if options.no_color {
+    if options.no_color || options.output_format != output::OutputFormat::Text {
         colored::control::set_override(false);
     } else if options.color {
         colored::control::set_override(true);
@@ -266,10 +883,93 @@ fn main() -> anyhow::Result<()> {
         options.files.push("-".to_string());
     }
 
+    if options.style() == FormatStyle::Sfv {
+        let given = |id| matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine);
+        if given("algorithm") || given("format") {
+            Options::command()
+                .error(
+                    clap::error::ErrorKind::ArgumentConflict,
+                    "--format-style sfv always uses CRC32 in uppercase hex, and can't be used with --algorithm or --format",
+                )
+                .exit();
+        }
+        options.algorithm = Algorithm::Crc32;
+        options.format = ChecksumFormat::HexUpper;
+    }
+
+    let generating = !options.check
+        && !options.check_sidecars
+        && options.sidecar.is_none()
+        && options.audit.is_none()
+        && options.sign.is_none()
+        && !options.duplicates
+        && !options.compare
+        && options.group_key().is_none();
+    if options.format == ChecksumFormat::Raw {
+        let plain = generating
+            && options.style() == FormatStyle::Gnu
+            && options.printf.is_none()
+            && options.output_format == output::OutputFormat::Text;
+        if !plain || options.files.len() != 1 {
+            Options::command()
+                .error(
+                    clap::error::ErrorKind::ArgumentConflict,
+                    "--format raw can only be used to print the checksum of a single file, without --tag or a --format-style other than gnu",
+                )
+                .exit();
+        }
+    }
+
+    let stdout_reports = options
+        .report
+        .iter()
+        .filter(|target| target.path.is_none())
+        .count();
+    if stdout_reports > 1
+        || (stdout_reports > 0 && options.output_format != output::OutputFormat::Text)
+    {
+        Options::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "only one report can be written to stdout, and not with --output-format json or ndjson",
+            )
+            .exit();
+    }
+
+    if options.style().is_manifest()
+        && (!generating || options.output_format != output::OutputFormat::Text)
+    {
+        Options::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                format!(
+                    "--format-style {} can only be used to print checksums as text",
+                    options.style()
+                ),
+            )
+            .exit();
+    }
+
     if options.check {
         do_check(&options)?;
-    } else if options.group.is_some() || options.group_with_check.is_some() {
+    } else if options.check_sidecars {
+        sidecar::do_check_sidecars(&options)?;
+    } else if let Some(style) = options.sidecar {
+        sidecar::do_sidecar(&options, style)?;
+    } else if let Some(key) = &options.sign {
+        signify::do_sign(&options, key)?;
+    } else if let Some(manifest) = &options.audit {
+        hashdeep::do_audit(&options, manifest)?;
+    } else if options.duplicates {
+        duplicates::do_duplicates(&options)?;
+    } else if options.compare {
+        compare::do_compare(&options)?;
+    } else if options.group_key().is_some() {
         do_checksum_with_group(&options)?;
+    } else if options.style() == FormatStyle::Hashdeep {
+        hashdeep::do_hashdeep(&options)?;
+    } else if options.style() == FormatStyle::Mtree {
+        mtree::do_mtree(&options)?;
     } else {
         do_checksum(&options)?;
     }
@@ -279,43 +979,58 @@ fn main() -> anyhow::Result<()> {
 
 fn do_checksum(options: &Options) -> anyhow::Result<()> {
     let mut anything_failed = false;
+    let mut writer = output::RecordWriter::new(options.output_format);
     for file in &options.files {
         let checksum = if file == "-" {
             checksum_stdin(options.algorithm, options.buffer_size)
         } else {
             checksum_file(file, options.algorithm, options.buffer_size)
         };
+        if !writer.is_text() {
+            writer.write(&checksum_record(options, file, &checksum))?;
+        }
         let checksum = match checksum {
             Ok(checksum) => checksum,
             Err(e) => {
-                eprintln!("{}: {}", file, e.to_string().red());
+                if writer.is_text() {
+                    eprintln!("{}: {}", file, e.to_string().red());
+                }
                 anything_failed = true;
                 continue;
             }
         };
+        if !writer.is_text() {
+            continue;
+        }
+
+        if options.format == ChecksumFormat::Raw {
+            std::io::stdout().write_all(&checksum)?;
+            continue;
+        }
 
-        let checksum_display = options.format.encode(&checksum);
+        let checksum_display = options.format.encode(options.algorithm, &checksum);
         let colored_checksum = utils::colorize_checksum(&checksum_display, &checksum, false);
 
+        if let Some(template) = &options.printf {
+            print_template(options, template, &colored_checksum, file, (None, file));
+            continue;
+        }
+
         let file_display = if options.zero {
             file.clone()
         } else {
             escape::escape(file)
         };
-        let line = if options.tag {
-            format!(
-                "{} ({}) = {}",
-                options.algorithm, file_display, colored_checksum
-            )
-        } else {
-            format!("{colored_checksum}  {file_display}")
-        };
+        let line = options.format_line(&colored_checksum, &file_display, file_display != *file);
         if options.zero {
             print!("{line}\0");
         } else {
             println!("{line}");
         }
     }
+    if !writer.is_text() {
+        writer.finish();
+    }
 
     if anything_failed {
         std::process::exit(1);
@@ -324,67 +1039,167 @@ fn do_checksum(options: &Options) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn print_template(
+    options: &Options,
+    template: &template::Template,
+    colored_checksum: &str,
+    file: &str,
+    (head, tail): (Option<&str>, &str),
+) {
+    let metadata = if template.needs_metadata() && file != "-" {
+        fs_err::metadata(file).ok()
+    } else {
+        None
+    };
+    print!(
+        "{}",
+        template.render(&template::Fields {
+            digest: colored_checksum,
+            path: file,
+            algorithm: &options.algorithm.to_string(),
+            head,
+            tail,
+            metadata: metadata.as_ref(),
+        })
+    );
+}
+
+fn checksum_record(
+    options: &Options,
+    file: &str,
+    checksum: &anyhow::Result<Vec<u8>>,
+) -> output::ChecksumRecord {
+    output::ChecksumRecord {
+        path: file.to_string(),
+        algorithm: options.algorithm.to_string(),
+        digest: checksum
+            .as_ref()
+            .ok()
+            .map(|checksum| options.format.encode(options.algorithm, checksum)),
+        size: if file == "-" {
+            None
+        } else {
+            fs_err::metadata(file).ok().map(|metadata| metadata.len())
+        },
+        group: None,
+        manifest: None,
+        error: checksum.as_ref().err().map(ToString::to_string),
+    }
+}
+
 fn do_checksum_with_group(options: &Options) -> anyhow::Result<()> {
     let mut anything_failed = false;
     let mut anything_group_failed = false;
     let mut anything_succeeded = false;
-    let n = options.group.or(options.group_with_check).unwrap() as usize;
+    let mut writer = output::RecordWriter::new(options.output_format);
+    let group_key = options.group_key().unwrap();
+
+    let mut entries = vec![];
+    if options.from_manifests {
+        for manifest in &options.files {
+            let mut anything_invalid = false;
+            entries.extend(read_manifest_entries(
+                options,
+                manifest,
+                &mut anything_invalid,
+            )?);
+            if anything_invalid && options.strict {
+                anything_failed = true;
+            }
+        }
+    } else {
+        entries.extend(options.files.iter().map(|file| GroupEntry {
+            manifest: None,
+            file: file.clone(),
+            checksum: None,
+        }));
+    }
 
     let mut groups = std::collections::HashMap::new();
-    for file in &options.files {
-        let (_head, tail) = utils::split_at_last_segments(file, n);
-        groups.entry(tail).or_insert_with(Vec::new).push(file);
+    for entry in entries {
+        let Some(key) = group_key.key(&entry.file) else {
+            eprintln!("{}: {}", entry.file, "no group key, skipped".yellow());
+            continue;
+        };
+        groups.entry(key).or_insert_with(Vec::new).push(entry);
     }
     let mut groups = groups.into_iter().collect::<Vec<_>>();
     groups.sort_by(|(a, _), (b, _)| a.cmp(b));
-    for (_tail, files) in groups {
-        let checksums = files
+    for (tail, entries) in groups {
+        let results = entries
             .iter()
-            .map(|&file| {
-                let checksum = if file == "-" {
+            .map(|entry| {
+                if let Some(checksum) = &entry.checksum {
+                    return Ok(checksum.clone());
+                }
+                let file = entry.file.as_str();
+                if file == "-" {
                     checksum_stdin(options.algorithm, options.buffer_size)
                 } else {
                     checksum_file(file, options.algorithm, options.buffer_size)
-                };
-                let checksum = match checksum {
-                    Ok(checksum) => checksum,
-                    Err(e) => {
-                        eprintln!("{}: {}", file, e.to_string().red());
-                        anything_failed = true;
-                        return None;
-                    }
-                };
-                Some(checksum)
+                }
             })
             .collect::<Vec<_>>();
+        let checksums = results
+            .iter()
+            .map(|result| result.as_ref().ok().cloned())
+            .collect::<Vec<_>>();
 
         let is_same = checksums.windows(2).all(|pair| pair[0] == pair[1]);
-
-        for (checksum, file) in checksums.iter().zip(files) {
-            let Some(checksum) = checksum else {
+        let consensus = utils::find_consensus(&checksums);
+
+        let mut outliers = vec![];
+        for (result, entry) in results.iter().zip(&entries) {
+            if !writer.is_text() {
+                let mut record = checksum_record(options, &entry.file, result);
+                record.group = Some(tail.clone());
+                if let Some(manifest) = entry.manifest {
+                    record.manifest = Some(manifest.to_string());
+                    record.size = None;
+                }
+                writer.write(&record)?;
+                continue;
+            }
+            let checksum = match result {
+                Ok(checksum) => checksum,
+                Err(e) => {
+                    eprintln!("{}: {}", entry.file, e.to_string().red());
+                    continue;
+                }
+            };
+            let is_outlier = consensus.is_none_or(|(digest, _)| digest != checksum.as_slice());
+            if is_outlier && !is_same {
+                outliers.push(match entry.manifest {
+                    Some(manifest) => format!("{} ({})", entry.file, manifest),
+                    None => entry.file.clone(),
+                });
+            }
+            let checksum_display = options.format.encode(options.algorithm, checksum);
+            let colored_checksum =
+                utils::colorize_checksum(&checksum_display, checksum, is_same || !is_outlier);
+
+            let (file_head, file_tail) = group_key.split(&entry.file);
+            if let Some(template) = &options.printf {
+                let split = (file_head.as_deref(), file_tail.as_str());
+                print_template(options, template, &colored_checksum, &entry.file, split);
                 continue;
+            }
+            // The source manifest would make the line unverifiable if it were saved as a manifest.
+            let file_head = match entry.manifest {
+                Some(manifest) if std::io::stdout().is_terminal() => {
+                    Some(format!("{manifest}: ") + &file_head.unwrap_or_default())
+                }
+                _ => file_head,
             };
-            let checksum_display = options.format.encode(checksum);
-            let colored_checksum = utils::colorize_checksum(&checksum_display, checksum, is_same);
-
-            let (file_head, file_tail) = utils::split_at_last_segments(file, n);
+            let file_head = file_head.unwrap_or_default();
+            let escaped = !options.zero && escape::escape(&file_head) != file_head;
             let file_display = if options.zero {
-                file_head.unwrap_or_default() + &file_tail
+                file_head + &file_tail
             } else {
-                escape::escape(&file_head.unwrap_or_default())
-                    .dimmed()
-                    .to_string()
-                    + &file_tail
+                escape::escape(&file_head).dimmed().to_string() + &file_tail
             };
 
-            let line = if options.tag {
-                format!(
-                    "{} ({}) = {}",
-                    options.algorithm, file_display, colored_checksum
-                )
-            } else {
-                format!("{colored_checksum}  {file_display}")
-            };
+            let line = options.format_line(&colored_checksum, &file_display, escaped);
             if options.zero {
                 print!("{line}\0");
             } else {
@@ -392,10 +1207,42 @@ fn do_checksum_with_group(options: &Options) -> anyhow::Result<()> {
             }
         }
 
+        let copies = checksums.iter().flatten().count();
+        if !is_same && copies > 1 && writer.is_text() {
+            match consensus {
+                Some((_, agreeing)) => eprintln!(
+                    "{}: {}",
+                    tail,
+                    format!(
+                        "{agreeing}/{copies} copies agree, outliers: {}",
+                        outliers.join(", ")
+                    )
+                    .yellow()
+                ),
+                None => eprintln!(
+                    "{}: {}",
+                    tail,
+                    format!("no consensus among {copies} copies").red()
+                ),
+            }
+        }
+
         if checksums.iter().any(Option::is_none) {
             anything_failed = true;
         }
-        if checksums.len() > 1 {
+        if let Some(quorum) = options.require_quorum {
+            let agreeing = consensus.map_or(0, |(_, agreeing)| agreeing);
+            if agreeing < quorum as usize {
+                eprintln!(
+                    "{}: {}",
+                    tail,
+                    format!("quorum not reached: {agreeing}/{quorum} copies agree").red()
+                );
+                anything_group_failed = true;
+            } else if checksums.len() > 1 {
+                anything_succeeded = true;
+            }
+        } else if checksums.len() > 1 {
             if is_same {
                 anything_succeeded = true;
             } else {
@@ -403,6 +1250,9 @@ fn do_checksum_with_group(options: &Options) -> anyhow::Result<()> {
             }
         }
     }
+    if !writer.is_text() {
+        writer.finish();
+    }
 
     if anything_failed {
         std::process::exit(1);
@@ -422,14 +1272,95 @@ fn do_checksum_with_group(options: &Options) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// A file in group mode, optionally with a checksum already read from a manifest.
+struct GroupEntry<'a> {
+    manifest: Option<&'a str>,
+    file: String,
+    checksum: Option<Vec<u8>>,
+}
+
+/// Reads the file names and checksums listed in `manifest`. `anything_invalid` is set if any line
+/// was improperly formatted or used a different algorithm.
+fn read_manifest_entries<'a>(
+    options: &Options,
+    manifest: &'a str,
+    anything_invalid: &mut bool,
+) -> anyhow::Result<Vec<GroupEntry<'a>>> {
+    let reader: Box<dyn BufRead> = if manifest == "-" {
+        Box::new(std::io::stdin().lock())
+    } else {
+        Box::new(std::io::BufReader::new(fs_err::File::open(manifest)?))
+    };
+
+    let mut entries = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        let entry = parse_line(&line, options.algorithm).and_then(|(algorithm, filename, hash)| {
+            if algorithm != options.algorithm {
+                return Err(CheckError::InvalidLine(format!(
+                    "algorithm `{algorithm}` does not match `{}`",
+                    options.algorithm
+                )));
+            }
+            let (checksum, _) = parse_checksum_format(&hash, algorithm, options.format)?;
+            Ok(GroupEntry {
+                manifest: Some(manifest),
+                file: filename,
+                checksum: Some(checksum),
+            })
+        });
+        match entry {
+            Ok(entry) => entries.push(entry),
+            Err(e) => {
+                *anything_invalid = true;
+                if options.warn {
+                    eprintln!("{}: {}", manifest, e.to_string().yellow());
+                }
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
 fn do_check(options: &Options) -> anyhow::Result<()> {
     let mut anything_succeeded = false;
     let mut anything_failed = false;
+    let mut writer = output::RecordWriter::new(options.output_format);
+    let mut reporter = report::Reporter::default();
+    let public_key = options
+        .pubkey
+        .as_deref()
+        .map(signify::PublicKey::read)
+        .transpose()?;
+    let allowed_signers = options
+        .allowed_signers
+        .as_deref()
+        .map(sshsig::AllowedSigners::read)
+        .transpose()?;
     for filepath in &options.files {
-        if filepath == "-" {
-            for line in std::io::stdin().lines() {
-                let line = line?;
-                match do_line(options, filepath, &line) {
+        let mut reader: Box<dyn BufRead> = if filepath == "-" {
+            Box::new(std::io::stdin().lock())
+        } else {
+            Box::new(std::io::BufReader::new(fs_err::File::open(filepath)?))
+        };
+        // Plain manifests are checked line by line as they are read. Signed manifests and other
+        // formats are read into memory first.
+        let mut contents = vec![];
+        if public_key.is_none()
+            && allowed_signers.is_none()
+            && !options.loose
+            && read_plain_head(&mut reader, &mut contents)?
+        {
+            let head = String::from_utf8(contents)?;
+            let lines = head
+                .lines()
+                .map(|line| Ok(line.to_string()))
+                .chain(reader.lines());
+            for (index, line) in lines.enumerate() {
+                let line = line.map_err(|e| anyhow::anyhow!("{filepath}: {e}"))?;
+                reporter.start_line(filepath, index + 1);
+                match do_line(options, &mut writer, &mut reporter, filepath, &line)? {
                     Some(true) => {
                         anything_succeeded = true;
                     }
@@ -439,22 +1370,204 @@ fn do_check(options: &Options) -> anyhow::Result<()> {
                     None => {}
                 }
             }
+            continue;
+        }
+        reader.read_to_end(&mut contents)?;
+        if let Some(public_key) = &public_key {
+            signify::verify_manifest(public_key, filepath, &contents)?;
+        }
+        if let Some(signers) = &allowed_signers {
+            sshsig::verify_manifest(
+                signers,
+                &options.namespace,
+                options.identity.as_deref().unwrap(),
+                filepath,
+                &contents,
+            )?;
+        }
+        let contents =
+            utils::decode_text(contents).map_err(|e| anyhow::anyhow!("{filepath}: {e}"))?;
+        // Only the signed body of a PGP signed manifest is checked.
+        let (contents, first_line) = match pgp::Cleartext::parse(&contents) {
+            Some(message) => {
+                let message = message.map_err(|e| anyhow::anyhow!("{filepath}: {e}"))?;
+                if message.unsigned_lines > 0 && writer.is_text() && !options.quiet {
+                    eprintln!(
+                        "{}: {}",
+                        filepath,
+                        format!(
+                            "ignoring {} lines outside the signed message",
+                            message.unsigned_lines
+                        )
+                        .yellow()
+                    );
+                }
+                if let Some(path) = &options.extract_signature {
+                    fs_err::write(path, &message.signature)?;
+                }
+                if let Some(path) = &options.extract_signed_body {
+                    fs_err::write(path, &message.body)?;
+                }
+                (message.body, message.first_line)
+            }
+            None => (contents, 1),
+        };
+
+        let results = if options.loose {
+            loose::do_loose(options, &mut writer, &mut reporter, filepath, &contents)?
+        } else if let Some(records) = output::parse_records(&contents) {
+            records
+                .into_iter()
+                .enumerate()
+                .map(|(index, record)| {
+                    reporter.start_line(filepath, index + 1);
+                    do_record(options, &mut writer, &mut reporter, filepath, record)
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?
+        } else if let Some(manifest) = hashdeep::Manifest::parse(&contents) {
+            do_hashdeep_manifest(options, &mut writer, &mut reporter, filepath, manifest?)?
+        } else if let Some(entries) = mtree::parse(&contents) {
+            mtree::do_mtree_manifest(options, &mut writer, &mut reporter, filepath, entries)?
+        } else if let Some(entries) = foreign::parse(&contents) {
+            do_foreign_entries(options, &mut writer, &mut reporter, filepath, entries)?
         } else {
-            let file = fs_err::File::open(filepath)?;
-            for line in std::io::BufReader::new(file).lines() {
-                let line = line?;
-                match do_line(options, filepath, &line) {
-                    Some(true) => {
-                        anything_succeeded = true;
-                    }
-                    Some(false) => {
-                        anything_failed = true;
-                    }
-                    None => {}
+            contents
+                .lines()
+                .enumerate()
+                .map(|(index, line)| {
+                    reporter.start_line(filepath, first_line + index);
+                    do_line(options, &mut writer, &mut reporter, filepath, line)
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?
+        };
+        for result in results {
+            match result {
+                Some(true) => {
+                    anything_succeeded = true;
+                }
+                Some(false) => {
+                    anything_failed = true;
                 }
+                None => {}
             }
         }
     }
+    finish_check(
+        options,
+        writer,
+        &reporter,
+        anything_succeeded,
+        anything_failed,
+    )
+}
+
+/// Reads the lines of a manifest up to its first checksum line into `head`, and returns whether
+/// the rest can be checked line by line. It can't if the manifest is UTF-16, has a byte order mark,
+/// has no checksum lines, or is in a format that is parsed as a whole.
+fn read_plain_head(reader: &mut dyn BufRead, head: &mut Vec<u8>) -> anyhow::Result<bool> {
+    if matches!(
+        reader.fill_buf()?,
+        [0xef, 0xbb, 0xbf, ..] | [0xff, 0xfe, ..] | [0xfe, 0xff, ..] | [_, 0, ..] | [0, _, ..]
+    ) {
+        return Ok(false);
+    }
+    loop {
+        let start = head.len();
+        if reader.read_until(b'\n', head)? == 0 {
+            return Ok(false);
+        }
+        let Ok(line) = std::str::from_utf8(&head[start..]) else {
+            return Ok(false);
+        };
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if let Ok((algorithm, _, hash)) = parse_line(line, Algorithm::SHA256)
+            && decode_checksum(&hash, algorithm, ChecksumFormat::Hex).is_ok()
+        {
+            break;
+        }
+    }
+
+    let head = std::str::from_utf8(head)?;
+    Ok(output::parse_records(head).is_none()
+        && hashdeep::Manifest::parse(head).is_none()
+        && mtree::parse(head).is_none()
+        && pgp::Cleartext::parse(head).is_none()
+        && foreign::parse(head).is_none())
+}
+
+fn do_hashdeep_manifest(
+    options: &Options,
+    writer: &mut output::RecordWriter,
+    reporter: &mut report::Reporter,
+    file: &str,
+    manifest: hashdeep::Manifest,
+) -> anyhow::Result<Vec<Option<bool>>> {
+    let Some((column, algorithm)) = manifest.column(options.algorithm) else {
+        anyhow::bail!("{file}: no hashes in the manifest");
+    };
+    manifest
+        .entries
+        .into_iter()
+        .map(|(line_number, entry)| {
+            reporter.start_line(file, line_number);
+            match entry {
+                Ok(entry) => do_entry(
+                    options,
+                    writer,
+                    reporter,
+                    file,
+                    algorithm,
+                    &entry.path,
+                    &entry.digests[column],
+                ),
+                Err(e) => {
+                    reporter.record_invalid(e.to_string());
+                    do_invalid_line(options, writer, file, e)
+                }
+            }
+        })
+        .collect()
+}
+
+fn do_foreign_entries(
+    options: &Options,
+    writer: &mut output::RecordWriter,
+    reporter: &mut report::Reporter,
+    file: &str,
+    entries: Vec<(usize, foreign::Entry)>,
+) -> anyhow::Result<Vec<Option<bool>>> {
+    entries
+        .into_iter()
+        .map(|(line_number, entry)| {
+            reporter.start_line(file, line_number);
+            match entry {
+                Ok((algorithm, filename, hash)) => {
+                    do_entry(options, writer, reporter, file, algorithm, &filename, &hash)
+                }
+                Err(e) => {
+                    reporter.record_invalid(e.to_string());
+                    do_invalid_line(options, writer, file, e)
+                }
+            }
+        })
+        .collect()
+}
+
+/// Writes the pending output and reports of check mode, and exits with the check status.
+fn finish_check(
+    options: &Options,
+    writer: output::RecordWriter,
+    reporter: &report::Reporter,
+    anything_succeeded: bool,
+    anything_failed: bool,
+) -> anyhow::Result<()> {
+    if !writer.is_text() {
+        writer.finish();
+    }
+    for target in &options.report {
+        reporter.write(target)?;
+    }
 
     if anything_failed {
         std::process::exit(1);
@@ -468,7 +1581,7 @@ fn do_check(options: &Options) -> anyhow::Result<()> {
 
 static BSD_REGEX: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|| {
     regex::Regex::new(&format!(
-        r#"^(?P<algorithm>{}) \((?P<filename>.+)\) = (?P<hash>[0-9a-fA-F]{{2}}+)$"#,
+        r#"^(?P<escaped>\\)?(?P<algorithm>{}) \((?P<filename>.+)\) = (?P<hash>\S+)$"#,
         Algorithm::iter()
             .map(|a| a.to_string())
             .collect::<Vec<_>>()
@@ -477,34 +1590,189 @@ static BSD_REGEX: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|
     .expect("invalid regex")
 });
 
-fn do_line(options: &Options, file: &str, line: &str) -> Option<bool> {
-    let mut ret = None;
-    let (algorithm, filename, hash) = match parse_line(line) {
-        Ok((algorithm, filename, hash)) => (algorithm, filename, hash),
-        Err(CheckError::InvalidLine(_)) if options.strict => {
-            ret = Some(false);
-            return ret;
-        }
-        Err(CheckError::InvalidLine(_)) if options.warn => {
-            eprintln!("{}: {}", file, "invalid line".yellow());
-            return ret;
+fn do_line(
+    options: &Options,
+    writer: &mut output::RecordWriter,
+    reporter: &mut report::Reporter,
+    file: &str,
+    line: &str,
+) -> anyhow::Result<Option<bool>> {
+    // SFV comments, e.g. `; Generated by ...`
+    if line.starts_with(';') {
+        return Ok(None);
+    }
+
+    // Like sha256sum, lines without an algorithm are SHA-256, whatever --algorithm says.
+    match parse_line(line, Algorithm::SHA256) {
+        Ok((algorithm, filename, hash)) => {
+            let filename = unescape_unmarked(options, file, line, filename);
+            do_entry(options, writer, reporter, file, algorithm, &filename, &hash)
         }
-        Err(CheckError::InvalidLine(_)) => {
-            return ret;
+        Err(e) => {
+            if !line.trim().is_empty() {
+                reporter.record_invalid(e.to_string());
+            }
+            do_invalid_line(options, writer, file, e)
         }
-        Err(_) => unreachable!(),
+    }
+}
+
+/// Older versions of ccsum escaped names without starting the line with a backslash. The name of
+/// such a line is unescaped if the file doesn't exist as written but does once unescaped.
+fn unescape_unmarked(options: &Options, file: &str, line: &str, filename: String) -> String {
+    if line.starts_with('\\') || !filename.contains('\\') {
+        return filename;
+    }
+    let Ok(unescaped) = escape::unescape(&filename) else {
+        return filename;
     };
+    let exists = |filename: &str| {
+        let (path, _) = paths::resolve(options, file, filename);
+        std::path::Path::new(&path).symlink_metadata().is_ok()
+    };
+    if !exists(&filename) && exists(&unescaped) {
+        unescaped
+    } else {
+        filename
+    }
+}
 
-    let result = process_line(algorithm, options.buffer_size, &filename, &hash);
-    match &result {
-        Ok(()) => {
-            ret = Some(true);
+fn do_record(
+    options: &Options,
+    writer: &mut output::RecordWriter,
+    reporter: &mut report::Reporter,
+    file: &str,
+    record: Result<output::ChecksumRecord, serde_json::Error>,
+) -> anyhow::Result<Option<bool>> {
+    let entry = record
+        .map_err(|e| CheckError::InvalidLine(e.to_string()))
+        .and_then(|record| {
+            let Some(digest) = record.digest else {
+                return Err(CheckError::InvalidLine(format!(
+                    "no digest for {}",
+                    record.path
+                )));
+            };
+            let Ok(algorithm) = record.algorithm.parse::<Algorithm>() else {
+                return Err(CheckError::InvalidLine(format!(
+                    "invalid algorithm: {}",
+                    record.algorithm
+                )));
+            };
+            Ok((algorithm, record.path, digest))
+        });
+    match entry {
+        Ok((algorithm, filename, hash)) => {
+            do_entry(options, writer, reporter, file, algorithm, &filename, &hash)
         }
-        Err(CheckError::InvalidLine(_)) if options.strict => {
-            ret = Some(false);
+        Err(e) => {
+            reporter.record_invalid(e.to_string());
+            do_invalid_line(options, writer, file, e)
         }
-        Err(CheckError::InvalidLine(_)) if options.warn => {
-            eprintln!("{}: {}", &filename, "invalid line".yellow());
+    }
+}
+
+fn do_invalid_line(
+    options: &Options,
+    writer: &mut output::RecordWriter,
+    file: &str,
+    error: CheckError,
+) -> anyhow::Result<Option<bool>> {
+    if !writer.is_text() && (options.strict || options.warn) {
+        writer.write(&output::CheckRecord {
+            manifest: file.to_string(),
+            path: None,
+            algorithm: None,
+            status: "invalid",
+            expected: None,
+            actual: None,
+            error_class: Some(error.class()),
+            error: Some(error.to_string()),
+            normalization: None,
+        })?;
+    }
+
+    match error {
+        CheckError::InvalidLine(_) if options.strict => Ok(Some(false)),
+        CheckError::InvalidLine(_) if options.warn => {
+            if writer.is_text() {
+                eprintln!("{}: {}", file, "invalid line".yellow());
+            }
+            Ok(None)
+        }
+        CheckError::InvalidLine(_) => Ok(None),
+        _ => unreachable!(),
+    }
+}
+
+fn do_entry(
+    options: &Options,
+    writer: &mut output::RecordWriter,
+    reporter: &mut report::Reporter,
+    file: &str,
+    algorithm: Algorithm,
+    filename: &str,
+    hash: &str,
+) -> anyhow::Result<Option<bool>> {
+    let (path, form) = paths::resolve(options, file, filename);
+    if let Some(form) = form
+        && writer.is_text()
+        && !options.quiet
+    {
+        eprintln!("{}: {}", filename, format!("found as {form}").yellow());
+    }
+    let result = process_line(algorithm, options.buffer_size, options.format, &path, hash);
+    if let Err(e @ CheckError::InvalidLine(_)) = result {
+        reporter.record_invalid(e.to_string());
+        return do_invalid_line(options, writer, file, e);
+    }
+    report_entry(
+        options,
+        writer,
+        reporter,
+        file,
+        (algorithm, filename, hash),
+        form,
+        result,
+    )
+}
+
+/// Reports the result of verifying a manifest entry. `hash` may be empty for entries without a
+/// digest, and `form` is the normalization form the file was found in, if any.
+fn report_entry(
+    options: &Options,
+    writer: &mut output::RecordWriter,
+    reporter: &mut report::Reporter,
+    file: &str,
+    (algorithm, filename, hash): (Algorithm, &str, &str),
+    form: Option<paths::Normalization>,
+    result: Result<Vec<u8>, CheckError>,
+) -> anyhow::Result<Option<bool>> {
+    let mut ret = None;
+    let normalization = form.map(|form| form.to_string());
+    reporter.record(
+        filename,
+        normalization.clone(),
+        match &result {
+            Ok(_) => report::Outcome::Passed,
+            Err(CheckError::ChecksumMismatch { expected, actual }) => report::Outcome::Failed {
+                expected: expected.clone(),
+                actual: actual.clone(),
+            },
+            Err(e @ CheckError::ReadFailed(_)) if options.ignore_missing => {
+                report::Outcome::Skipped {
+                    message: e.to_string(),
+                }
+            }
+            Err(e) => report::Outcome::Error {
+                class: e.class(),
+                message: e.to_string(),
+            },
+        },
+    );
+    match &result {
+        Ok(_) => {
+            ret = Some(true);
         }
         Err(CheckError::ReadFailed(_)) if options.ignore_missing => {}
         Err(_) => {
@@ -512,32 +1780,91 @@ fn do_line(options: &Options, file: &str, line: &str) -> Option<bool> {
         }
     }
 
+    if !writer.is_text() {
+        let (expected, actual) = match &result {
+            Ok(actual) if actual.is_empty() => (None, None),
+            Ok(actual) => (Some(hex::encode(actual)), Some(hex::encode(actual))),
+            Err(CheckError::ChecksumMismatch { expected, actual }) => {
+                (Some(expected.clone()), Some(actual.clone()))
+            }
+            Err(_) => (
+                parse_checksum_format(hash, algorithm, options.format)
+                    .ok()
+                    .map(|(expected, _)| hex::encode(expected)),
+                None,
+            ),
+        };
+        let status = match &result {
+            Ok(_) => "ok",
+            Err(CheckError::ChecksumMismatch { .. }) => "mismatch",
+            Err(CheckError::MetadataMismatch(_)) => "metadata",
+            Err(CheckError::ReadFailed(_)) if options.ignore_missing => "ignored",
+            Err(CheckError::ReadFailed(_)) => "unreadable",
+            Err(_) => "invalid",
+        };
+        writer.write(&output::CheckRecord {
+            manifest: file.to_string(),
+            path: Some(filename.to_string()),
+            algorithm: Some(algorithm.to_string()),
+            status,
+            expected,
+            actual,
+            error_class: result.as_ref().err().map(CheckError::class),
+            error: result.as_ref().err().map(ToString::to_string),
+            normalization,
+        })?;
+        return Ok(ret);
+    }
+
     if !options.quiet {
         match result {
-            Ok(()) => {
-                println!("{}: {}", &filename, "OK".green());
+            Ok(_) => {
+                if !options.report_to_stdout() {
+                    println!("{}: {}", filename, "OK".green());
+                }
             }
             Err(CheckError::ReadFailed(e)) if options.ignore_missing => {
-                eprintln!("{}: {}", &filename, e.to_string().yellow());
+                eprintln!("{}: {}", filename, e.to_string().yellow());
             }
             Err(e) => {
-                eprintln!("{}: {}", &filename, e.to_string().red());
+                eprintln!("{}: {}", filename, e.to_string().red());
             }
         }
     }
 
-    ret
+    Ok(ret)
 }
 
-fn parse_line(line: &str) -> Result<(Algorithm, String, String), CheckError> {
-    if let Some((_, hash, filename)) =
-        lazy_regex::regex_captures!("^([0-9a-fA-F]{2}+)  (.+)$", line)
+/// Parses an openssl, GNU, BSD or SFV line. Like coreutils, file names in GNU and BSD lines are
+/// only unescaped if the line starts with a backslash, so that backslashes in Windows paths are
+/// kept.
+fn parse_line(
+    line: &str,
+    default_algorithm: Algorithm,
+) -> Result<(Algorithm, String, String), CheckError> {
+    // openssl dgst: `SHA2-256(filename)= hash`
+    if let Some((_, name, filename, hash)) =
+        lazy_regex::regex_captures!(r"^([\w-]+)\((.+)\)= ([0-9A-Fa-f]+)$", line)
     {
-        let hash = hash.to_string();
-        let filename = filename.to_string();
-        let algorithm = Algorithm::SHA256;
+        let algorithm = Algorithm::from_name(name)
+            .ok_or_else(|| CheckError::InvalidLine(format!("unknown algorithm: {name}")))?;
+        return Ok((algorithm, filename.to_string(), hash.to_string()));
+    }
 
-        Ok((algorithm, escape::unescape(&filename)?, hash))
+    let unescape = |escaped: &str, filename: &str| {
+        if escaped.is_empty() {
+            Ok(filename.to_string())
+        } else {
+            escape::unescape(filename).map_err(|e| CheckError::InvalidLine(e.to_string()))
+        }
+    };
+
+    // `*` marks files read in binary mode, as written by `sha256sum -b` and on Windows.
+    if let Some((_, escaped, hash, filename)) =
+        lazy_regex::regex_captures!(r"^(\\?)(\S+) [ *](.+)$", line)
+    {
+        let algorithm = Algorithm::from_digest(hash).unwrap_or(default_algorithm);
+        Ok((algorithm, unescape(escaped, filename)?, hash.to_string()))
     } else if let Some(captures) = BSD_REGEX.captures(line) {
         let algorithm = captures
             .name("algorithm")
@@ -550,29 +1877,54 @@ fn parse_line(line: &str) -> Result<(Algorithm, String, String), CheckError> {
                     captures.name("algorithm").unwrap().as_str()
                 ))
             })?;
-        let filename = captures.name("filename").unwrap().as_str();
+        let escaped = captures
+            .name("escaped")
+            .map_or("", |escaped| escaped.as_str());
+        let filename = unescape(escaped, captures.name("filename").unwrap().as_str())?;
         let hash = captures.name("hash").unwrap().as_str();
+        if let Some(described) = Algorithm::from_digest(hash)
+            && described != algorithm
+        {
+            return Err(CheckError::InvalidLine(format!(
+                "digest is for `{described}`, not `{algorithm}`"
+            )));
+        }
 
-        Ok((algorithm, filename.to_string(), hash.to_string()))
+        Ok((algorithm, filename, hash.to_string()))
+    } else if let Some((_, filename, hash)) =
+        lazy_regex::regex_captures!(r"^(.+) ([0-9A-Fa-f]{8})$", line)
+    {
+        // SFV: `filename CRC32`
+        Ok((Algorithm::Crc32, filename.to_string(), hash.to_string()))
     } else {
         Err(CheckError::InvalidLine("pattern not matched".to_string()))
     }
 }
 
-fn parse_checksum_format(
+/// Decodes `source` with every format that yields a digest of the right length for `algorithm`,
+/// `preferred` first. Some encodings are ambiguous, e.g. a base58 digest may also be valid
+/// unpadded base64.
+fn decode_checksum(
     source: &str,
     algorithm: Algorithm,
-) -> Result<(Vec<u8>, ChecksumFormat), CheckError> {
+    preferred: ChecksumFormat,
+) -> Result<Vec<(Vec<u8>, ChecksumFormat)>, CheckError> {
+    let mut decoded = vec![];
     let mut len_not_matched = vec![];
-    for fmt in ChecksumFormat::iter() {
-        if let Ok(bytes) = fmt.decode(source) {
+    let formats =
+        std::iter::once(preferred).chain(ChecksumFormat::iter().filter(|&fmt| fmt != preferred));
+    for fmt in formats {
+        if let Ok(bytes) = fmt.decode(algorithm, source) {
             if bytes.len() == algorithm.bytes_len() {
-                return Ok((bytes, fmt));
+                decoded.push((bytes, fmt));
             } else {
                 len_not_matched.push(fmt);
             }
         }
     }
+    if !decoded.is_empty() {
+        return Ok(decoded);
+    }
 
     Err(CheckError::InvalidLine(format!(
         "failed to parse checksum: `{}` for algorithm `{}`. Expected length: {}, candidates: {:?}",
@@ -583,21 +1935,32 @@ fn parse_checksum_format(
     )))
 }
 
+/// Decodes `source` with the first format that yields a digest of the right length for
+/// `algorithm`, trying `preferred` first.
+fn parse_checksum_format(
+    source: &str,
+    algorithm: Algorithm,
+    preferred: ChecksumFormat,
+) -> Result<(Vec<u8>, ChecksumFormat), CheckError> {
+    decode_checksum(source, algorithm, preferred).map(|mut decoded| decoded.swap_remove(0))
+}
+
+/// Verifies the file at `path` against `hash`. The file matches if any decoding of an ambiguous
+/// `hash` does.
 fn process_line(
     algorithm: Algorithm,
     buffer_size: usize,
-
-    filename: &str,
+    format: ChecksumFormat,
+    path: &str,
     hash: &str,
-) -> Result<(), CheckError> {
-    let actual = checksum_file(filename, algorithm, buffer_size).map_err(CheckError::ReadFailed)?;
-    let (expected, _) =
-        parse_checksum_format(hash, algorithm).expect("unreachable: already validated");
-    if actual == expected {
-        Ok(())
+) -> Result<Vec<u8>, CheckError> {
+    let decoded = decode_checksum(hash, algorithm, format)?;
+    let actual = checksum_file(path, algorithm, buffer_size).map_err(CheckError::ReadFailed)?;
+    if decoded.iter().any(|(expected, _)| *expected == actual) {
+        Ok(actual)
     } else {
         Err(CheckError::ChecksumMismatch {
-            expected: hex::encode(expected),
+            expected: hex::encode(&decoded[0].0),
             actual: hex::encode(actual),
         })
     }
@@ -611,9 +1974,25 @@ enum CheckError {
     #[error("checksum mismatch: expected {expected}, got {actual}")]
     ChecksumMismatch { expected: String, actual: String },
 
+    #[error("metadata mismatch: {0}")]
+    MetadataMismatch(String),
+
     #[error("invalid line: {0}")]
     InvalidLine(String),
 
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
+
+impl CheckError {
+    /// A stable name of the error kind, for machine-readable output.
+    fn class(&self) -> &'static str {
+        match self {
+            CheckError::ReadFailed(_) => "read_failed",
+            CheckError::ChecksumMismatch { .. } => "checksum_mismatch",
+            CheckError::MetadataMismatch(_) => "metadata_mismatch",
+            CheckError::InvalidLine(_) => "invalid_line",
+            CheckError::Other(_) => "other",
+        }
+    }
+}
@@ -0,0 +1,507 @@
+//! BSD mtree specifications: one line per path with `keyword=value` pairs describing its type,
+//! permissions, owner, size, link target and digests.
+
+use crate::{Algorithm, CheckError, Options, checksum_file, output, report};
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// The mtree keyword for a digest of `algorithm`, if mtree has one.
+fn digest_keyword(algorithm: Algorithm) -> Option<&'static str> {
+    match algorithm {
+        Algorithm::MD5 => Some("md5digest"),
+        Algorithm::SHA1 => Some("sha1digest"),
+        Algorithm::SHA256 => Some("sha256digest"),
+        Algorithm::SHA384 => Some("sha384digest"),
+        Algorithm::SHA512 => Some("sha512digest"),
+        _ => None,
+    }
+}
+
+/// Keywords that mtree allows without a value. They are accepted, but don't change how entries are
+/// verified.
+const BARE_KEYWORDS: [&str; 3] = ["ignore", "nochange", "optional"];
+
+/// Encodes a path like vis(3): whitespace, control characters, non-ASCII bytes and characters
+/// special to mtree are written as `\ooo`.
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for &byte in path.as_bytes() {
+        if byte <= b' ' || byte >= 0x7f || b"\\#=*?[".contains(&byte) {
+            write!(encoded, "\\{byte:03o}").unwrap();
+        } else {
+            encoded.push(byte as char);
+        }
+    }
+    encoded
+}
+
+fn decode_path(path: &str) -> String {
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        let octal = rest
+            .get(..3)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        if let Some(value) = octal {
+            bytes.push(value);
+            rest = &rest[3..];
+            continue;
+        }
+        let Some((&escaped, tail)) = rest.split_first() else {
+            bytes.push(b'\\');
+            break;
+        };
+        rest = tail;
+        bytes.push(match escaped {
+            b's' => b' ',
+            b't' => b'\t',
+            b'n' => b'\n',
+            b'r' => b'\r',
+            c => c,
+        });
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+pub struct Entry {
+    pub path: String,
+    pub keywords: BTreeMap<String, String>,
+}
+
+impl Entry {
+    /// The digest to verify the file with: the one for `preferred` if present, or the strongest.
+    fn digest(&self, preferred: Algorithm) -> Option<(Algorithm, &str)> {
+        let find = |algorithm: Algorithm| {
+            let keyword = digest_keyword(algorithm)?;
+            let digest = self
+                .keywords
+                .get(keyword)
+                .or_else(|| self.keywords.get(keyword.trim_end_matches("digest")))?;
+            Some((algorithm, digest.as_str()))
+        };
+        find(preferred).or_else(|| {
+            [
+                Algorithm::SHA512,
+                Algorithm::SHA384,
+                Algorithm::SHA256,
+                Algorithm::SHA1,
+                Algorithm::MD5,
+            ]
+            .into_iter()
+            .find_map(find)
+        })
+    }
+}
+
+/// Parses an mtree specification, in either the full-path or the hierarchical format. Returns
+/// `None` if `contents` doesn't look like one.
+pub fn parse(contents: &str) -> Option<Vec<(usize, Result<Entry, CheckError>)>> {
+    let first = contents
+        .lines()
+        .find(|line| !line.trim().is_empty() && (!line.starts_with('#') || *line == "#mtree"))?;
+    if first != "#mtree" && !first.starts_with("/set ") {
+        return None;
+    }
+
+    let mut entries = vec![];
+    let mut defaults = BTreeMap::new();
+    let mut cwd: Vec<String> = vec![];
+    let mut pending = String::new();
+    for (index, line) in contents.lines().enumerate() {
+        // Lines ending with a backslash continue on the next line.
+        if let Some(line) = line.strip_suffix('\\') {
+            pending.push_str(line);
+            pending.push(' ');
+            continue;
+        }
+        let line = std::mem::take(&mut pending) + line;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut words = line.split_whitespace();
+        let name = words.next().expect("line is not empty");
+        match name {
+            "/set" => {
+                for word in words {
+                    if let Some((keyword, value)) = word.split_once('=') {
+                        defaults.insert(keyword.to_string(), value.to_string());
+                    } else if BARE_KEYWORDS.contains(&word) {
+                        defaults.insert(word.to_string(), String::new());
+                    }
+                }
+                continue;
+            }
+            "/unset" => {
+                for word in words {
+                    if word == "all" {
+                        defaults.clear();
+                    } else {
+                        defaults.remove(word);
+                    }
+                }
+                continue;
+            }
+            ".." => {
+                cwd.pop();
+                continue;
+            }
+            _ => {}
+        }
+
+        let mut keywords = defaults.clone();
+        let mut invalid = None;
+        for word in words {
+            match word.split_once('=') {
+                Some((keyword, value)) => {
+                    keywords.insert(keyword.to_string(), value.to_string());
+                }
+                None if BARE_KEYWORDS.contains(&word) => {
+                    keywords.insert(word.to_string(), String::new());
+                }
+                None => invalid = Some(word),
+            }
+        }
+        if let Some(word) = invalid {
+            entries.push((
+                index + 1,
+                Err(CheckError::InvalidLine(format!(
+                    "invalid mtree keyword: {word}"
+                ))),
+            ));
+            continue;
+        }
+
+        let name = decode_path(name);
+        let path = if name.contains('/') {
+            name
+        } else {
+            let path = cwd
+                .iter()
+                .map(String::as_str)
+                .chain(std::iter::once(name.as_str()))
+                .collect::<Vec<_>>()
+                .join("/");
+            if name != "." && keywords.get("type").map(String::as_str) == Some("dir") {
+                cwd.push(name);
+            }
+            path
+        };
+        entries.push((index + 1, Ok(Entry { path, keywords })));
+    }
+
+    Some(entries)
+}
+
+fn file_type(metadata: &std::fs::Metadata) -> &'static str {
+    let file_type = metadata.file_type();
+    if file_type.is_dir() {
+        return "dir";
+    } else if file_type.is_symlink() {
+        return "link";
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+
+        if file_type.is_block_device() {
+            return "block";
+        } else if file_type.is_char_device() {
+            return "char";
+        } else if file_type.is_fifo() {
+            return "fifo";
+        } else if file_type.is_socket() {
+            return "socket";
+        }
+    }
+    "file"
+}
+
+/// The mode, uid and gid of a file, where the platform has them.
+#[cfg(unix)]
+fn ownership(metadata: &std::fs::Metadata) -> Option<(u32, u32, u32)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((metadata.mode() & 0o7777, metadata.uid(), metadata.gid()))
+}
+
+#[cfg(not(unix))]
+fn ownership(_metadata: &std::fs::Metadata) -> Option<(u32, u32, u32)> {
+    None
+}
+
+/// Returns the differences between the metadata in `entry` and the file on disk.
+fn metadata_differences(
+    entry: &Entry,
+    path: &str,
+    metadata: &std::fs::Metadata,
+) -> anyhow::Result<Vec<String>> {
+    let mut differences = vec![];
+    let mut compare = |keyword: &str, actual: String| {
+        if let Some(expected) = entry.keywords.get(keyword)
+            && *expected != actual
+        {
+            differences.push(format!("{keyword} expected {expected}, got {actual}"));
+        }
+    };
+
+    let file_type = file_type(metadata);
+    compare("type", file_type.to_string());
+    if let Some((mode, uid, gid)) = ownership(metadata) {
+        let expected_mode = entry
+            .keywords
+            .get("mode")
+            .and_then(|mode| u32::from_str_radix(mode, 8).ok());
+        if expected_mode.is_some_and(|expected| expected != mode) {
+            compare("mode", format!("{mode:04o}"));
+        }
+        compare("uid", uid.to_string());
+        compare("gid", gid.to_string());
+    }
+    if file_type == "file" {
+        compare("size", metadata.len().to_string());
+    }
+    if file_type == "link" {
+        let target = fs_err::read_link(path)?;
+        compare("link", encode_path(&target.to_string_lossy()));
+    }
+
+    Ok(differences)
+}
+
+/// Verifies the entries of an mtree specification. Metadata differences and content mismatches
+/// are reported as separate results.
+pub fn do_mtree_manifest(
+    options: &Options,
+    writer: &mut output::RecordWriter,
+    reporter: &mut report::Reporter,
+    file: &str,
+    entries: Vec<(usize, Result<Entry, CheckError>)>,
+) -> anyhow::Result<Vec<Option<bool>>> {
+    let mut results = vec![];
+    for (line_number, entry) in entries {
+        reporter.start_line(file, line_number);
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                reporter.record_invalid(e.to_string());
+                results.push(crate::do_invalid_line(options, writer, file, e)?);
+                continue;
+            }
+        };
+        let digest = entry.digest(options.algorithm);
+        let (algorithm, hash) = digest.unwrap_or((options.algorithm, ""));
+
+        let (path, form) = crate::paths::resolve(options, file, &entry.path);
+        let metadata = match fs_err::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                let result = Err(CheckError::ReadFailed(e.into()));
+                results.push(crate::report_entry(
+                    options,
+                    writer,
+                    reporter,
+                    file,
+                    (algorithm, &entry.path, hash),
+                    form,
+                    result,
+                )?);
+                continue;
+            }
+        };
+        let differences =
+            metadata_differences(&entry, &path, &metadata).map_err(CheckError::ReadFailed);
+        let metadata_result = match differences {
+            Ok(differences) if differences.is_empty() => None,
+            Ok(differences) => Some(Err(CheckError::MetadataMismatch(differences.join(", ")))),
+            Err(e) => Some(Err(e)),
+        };
+        let has_content = digest.is_some() && metadata.is_file();
+        if let Some(result) = metadata_result {
+            results.push(crate::report_entry(
+                options,
+                writer,
+                reporter,
+                file,
+                (algorithm, &entry.path, hash),
+                form,
+                result,
+            )?);
+        } else if !has_content {
+            results.push(crate::report_entry(
+                options,
+                writer,
+                reporter,
+                file,
+                (algorithm, &entry.path, hash),
+                form,
+                Ok(vec![]),
+            )?);
+        }
+        if has_content {
+            results.push(crate::do_entry(
+                options,
+                writer,
+                reporter,
+                file,
+                algorithm,
+                &entry.path,
+                hash,
+            )?);
+        }
+    }
+
+    Ok(results)
+}
+
+/// Formats the mtree keywords of a file.
+fn format_entry(
+    options: &Options,
+    path: &str,
+    metadata: &std::fs::Metadata,
+) -> anyhow::Result<String> {
+    let mut line = encode_path(path);
+    let file_type = file_type(metadata);
+    write!(line, " type={file_type}")?;
+    if let Some((mode, uid, gid)) = ownership(metadata) {
+        write!(line, " mode={mode:04o} uid={uid} gid={gid}")?;
+    }
+    match file_type {
+        "file" => {
+            let keyword = digest_keyword(options.algorithm).expect("validated in do_mtree");
+            let digest = checksum_file(path, options.algorithm, options.buffer_size)?;
+            write!(
+                line,
+                " size={} {keyword}={}",
+                metadata.len(),
+                hex::encode(digest)
+            )?;
+        }
+        "link" => {
+            let target = fs_err::read_link(path)?;
+            write!(line, " link={}", encode_path(&target.to_string_lossy()))?;
+        }
+        _ => {}
+    }
+
+    Ok(line)
+}
+
+/// Prints an mtree specification of the given paths, in the full-path format.
+pub fn do_mtree(options: &Options) -> anyhow::Result<()> {
+    if digest_keyword(options.algorithm).is_none() {
+        anyhow::bail!("mtree has no digest keyword for {}", options.algorithm);
+    }
+
+    let mut anything_failed = false;
+    println!("#mtree");
+    let mut stack = options.files.iter().rev().cloned().collect::<Vec<_>>();
+    while let Some(path) = stack.pop() {
+        if path == "-" {
+            eprintln!("{}: {}", path, "stdin is not supported here".red());
+            anything_failed = true;
+            continue;
+        }
+        // Paths without a slash would be read as relative to the previous directory.
+        let display = if path == "." || path.contains('/') {
+            path.clone()
+        } else {
+            format!("./{path}")
+        };
+        let result = fs_err::symlink_metadata(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|metadata| {
+                let line = format_entry(options, &display, &metadata)?;
+                if metadata.is_dir() {
+                    let mut children = fs_err::read_dir(&path)?
+                        .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+                        .collect::<anyhow::Result<Vec<_>>>()?;
+                    children.sort();
+                    let base = display.trim_end_matches('/');
+                    stack.extend(children.iter().rev().map(|name| format!("{base}/{name}")));
+                }
+                Ok(line)
+            });
+        match result {
+            Ok(line) => println!("{line}"),
+            Err(e) => {
+                eprintln!("{}: {}", path, e.to_string().red());
+                anything_failed = true;
+            }
+        }
+    }
+
+    if anything_failed {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_encoding() {
+        assert_eq!(encode_path("./a b#c.txt"), "./a\\040b\\043c.txt");
+        assert_eq!(decode_path("./a\\040b\\043c.txt"), "./a b#c.txt");
+        assert_eq!(decode_path(&encode_path("./日本")), "./日本");
+        assert_eq!(decode_path("a\\sb"), "a b");
+    }
+
+    #[test]
+    fn test_parse() {
+        let entries = parse(
+            "#\t   user: root\n\n/set type=file uid=0 gid=0 mode=0644\n. type=dir\nbin type=dir mode=0755\n    sh size=10 \\\n        sha256digest=abcd\n..\netc type=dir\n    passwd\n..\n./full/path size=3\nbad keyword\n",
+        )
+        .unwrap();
+        let entries = entries
+            .into_iter()
+            .map(|(line_number, entry)| {
+                (line_number, entry.map(|entry| (entry.path, entry.keywords)))
+            })
+            .collect::<Vec<_>>();
+        let paths = entries
+            .iter()
+            .map(|(line_number, entry)| {
+                (
+                    *line_number,
+                    entry.as_ref().ok().map(|(path, _)| path.as_str()),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                (4, Some(".")),
+                (5, Some("bin")),
+                (7, Some("bin/sh")),
+                (9, Some("etc")),
+                (10, Some("etc/passwd")),
+                (12, Some("./full/path")),
+                (13, None),
+            ]
+        );
+        let (_, sh) = entries[2].1.as_ref().unwrap();
+        assert_eq!(sh["sha256digest"], "abcd");
+        assert_eq!(sh["mode"], "0644");
+        assert_eq!(sh["type"], "file");
+
+        let entries = parse("/set type=file nochange\nfile optional size=3\n").unwrap();
+        let (_, entry) = &entries[0];
+        let keywords = &entry.as_ref().unwrap().keywords;
+        assert!(keywords.contains_key("nochange") && keywords.contains_key("optional"));
+
+        assert!(parse("abcd  file\n").is_none());
+    }
+}
@@ -0,0 +1,138 @@
+//! Self-describing digests: multihash (varint code + varint length + digest) and CIDv1 raw
+//! leaves.
+
+use crate::Algorithm;
+use strum::IntoEnumIterator;
+
+/// The multicodec code for `raw` binary content in CIDs.
+const RAW_CODEC: u64 = 0x55;
+
+fn code(algorithm: Algorithm) -> u64 {
+    match algorithm {
+        Algorithm::MD5 => 0xd5,
+        Algorithm::SHA1 => 0x11,
+        Algorithm::SHA224 => 0x1013,
+        Algorithm::SHA256 => 0x12,
+        Algorithm::SHA384 => 0x20,
+        Algorithm::SHA512 => 0x13,
+        Algorithm::Xxh32 => 0xb3e1,
+        Algorithm::Xxh64 => 0xb3e2,
+        Algorithm::Xxh3 => 0xb3e3,
+        Algorithm::Crc32 => 0x0132,
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..63).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+fn multihash_bytes(algorithm: Algorithm, digest: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![];
+    write_varint(&mut bytes, code(algorithm));
+    write_varint(&mut bytes, digest.len() as u64);
+    bytes.extend_from_slice(digest);
+    bytes
+}
+
+fn parse_multihash_bytes(mut bytes: &[u8]) -> Option<(Algorithm, Vec<u8>)> {
+    let code = read_varint(&mut bytes)?;
+    let len = read_varint(&mut bytes)?;
+    let algorithm = Algorithm::iter().find(|&algorithm| self::code(algorithm) == code)?;
+    if len as usize != bytes.len() || bytes.len() != algorithm.bytes_len() {
+        return None;
+    }
+
+    Some((algorithm, bytes.to_vec()))
+}
+
+/// Encodes a multihash as base58btc, the form used by IPFS tools.
+pub fn encode_multihash(algorithm: Algorithm, digest: &[u8]) -> String {
+    bs58::encode(multihash_bytes(algorithm, digest)).into_string()
+}
+
+pub fn decode_multihash(source: &str) -> Option<(Algorithm, Vec<u8>)> {
+    parse_multihash_bytes(&bs58::decode(source).into_vec().ok()?)
+}
+
+/// Encodes a CIDv1 of a raw leaf as multibase base32, e.g. `bafkrei...`.
+pub fn encode_cid(algorithm: Algorithm, digest: &[u8]) -> String {
+    let mut bytes = vec![];
+    write_varint(&mut bytes, 1);
+    write_varint(&mut bytes, RAW_CODEC);
+    bytes.extend(multihash_bytes(algorithm, digest));
+    "b".to_string() + &base32::encode(base32::Alphabet::Rfc4648Lower { padding: false }, &bytes)
+}
+
+/// Decodes a CIDv1 in multibase base32, base58btc or hex. Any content codec is accepted, as only
+/// the digest is verified.
+pub fn decode_cid(source: &str) -> Option<(Algorithm, Vec<u8>)> {
+    let mut chars = source.chars();
+    let bytes = match chars.next()? {
+        'b' => base32::decode(
+            base32::Alphabet::Rfc4648Lower { padding: false },
+            chars.as_str(),
+        )?,
+        'z' => bs58::decode(chars.as_str()).into_vec().ok()?,
+        'f' => hex::decode(chars.as_str()).ok()?,
+        _ => return None,
+    };
+    let mut bytes = bytes.as_slice();
+    if read_varint(&mut bytes)? != 1 {
+        return None;
+    }
+    read_varint(&mut bytes)?;
+
+    parse_multihash_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multihash() {
+        // sha256 of "hello world\n"
+        let digest =
+            hex::decode("a948904f2f0f479b8f8197694b30184b0d2ed1c1cd2a1ec0fb85d299a192a447")
+                .unwrap();
+        let multihash = encode_multihash(Algorithm::SHA256, &digest);
+        assert_eq!(multihash, "QmZjTnYw2TFhn9Nn7tjmPSoTBoY7YRkwPzwSrSbabY24Kp");
+        assert_eq!(
+            decode_multihash(&multihash),
+            Some((Algorithm::SHA256, digest.clone()))
+        );
+
+        let cid = encode_cid(Algorithm::SHA256, &digest);
+        assert_eq!(
+            cid,
+            "bafkreifjjcie6lypi6ny7amxnfftagclbuxndqonfipmb64f2km2devei4"
+        );
+        assert_eq!(decode_cid(&cid), Some((Algorithm::SHA256, digest)));
+    }
+
+    #[test]
+    fn test_varint() {
+        let mut bytes = vec![];
+        write_varint(&mut bytes, 0x1013);
+        assert_eq!(bytes, [0x93, 0x20]);
+        assert_eq!(read_varint(&mut bytes.as_slice()), Some(0x1013));
+    }
+}
@@ -0,0 +1,68 @@
+//! Nix's base32 encoding, which uses its own alphabet and encodes bytes starting from the end.
+
+const ALPHABET: &[u8; 32] = b"0123456789abcdfghijklmnpqrsvwxyz";
+
+pub fn encode(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        return String::new();
+    }
+
+    let len = (bytes.len() * 8 - 1) / 5 + 1;
+    let mut encoded = String::with_capacity(len);
+    for n in (0..len).rev() {
+        let b = n * 5;
+        let (i, j) = (b / 8, b % 8);
+        let low = bytes[i] >> j;
+        let high = if i + 1 < bytes.len() {
+            ((bytes[i + 1] as u16) << (8 - j)) as u8
+        } else {
+            0
+        };
+        encoded.push(ALPHABET[((low | high) & 0x1f) as usize] as char);
+    }
+
+    encoded
+}
+
+pub fn decode(source: &str) -> anyhow::Result<Vec<u8>> {
+    let size = source.len() * 5 / 8;
+    if size == 0 || (size * 8 - 1) / 5 + 1 != source.len() {
+        anyhow::bail!("invalid nix base32 length");
+    }
+
+    let mut bytes = vec![0u8; size];
+    for (n, c) in source.bytes().rev().enumerate() {
+        let digit = ALPHABET
+            .iter()
+            .position(|&a| a == c)
+            .ok_or_else(|| anyhow::anyhow!("invalid nix base32 character: {}", c as char))?
+            as u16;
+        let b = n * 5;
+        let (i, j) = (b / 8, b % 8);
+        bytes[i] |= (digit << j) as u8;
+        let carry = (digit << j) >> 8;
+        if i + 1 < size {
+            bytes[i + 1] |= carry as u8;
+        } else if carry != 0 {
+            anyhow::bail!("invalid nix base32 digest");
+        }
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nix32() {
+        // sha256 of an empty string
+        let bytes = hex::decode("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+            .unwrap();
+        let encoded = "0mdqa9w1p6cmli6976v4wi0sw9r4p5prkj7lzfd1877wk11c9c73";
+        assert_eq!(encode(&bytes), encoded);
+        assert_eq!(decode(encoded).unwrap(), bytes);
+        assert!(decode("0mdqa9w1p6cmli6976v4wi0sw9r4p5prkj7lzfd1877wk11c9c7e").is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, strum::Display)]
+pub enum OutputFormat {
+    #[clap(name = "text")]
+    Text,
+    #[clap(name = "json")]
+    Json,
+    #[clap(name = "ndjson", alias = "jsonl")]
+    Ndjson,
+}
+
+/// A checksum generated in normal or group mode. This is also accepted as a manifest entry in
+/// check mode.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChecksumRecord {
+    pub path: String,
+    pub algorithm: String,
+    pub digest: Option<String>,
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// The result of a manifest line in check mode. Digests are always hex.
+#[derive(Debug, Serialize)]
+pub struct CheckRecord {
+    pub manifest: String,
+    pub path: Option<String>,
+    pub algorithm: Option<String>,
+    pub status: &'static str,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+    pub error_class: Option<&'static str>,
+    pub error: Option<String>,
+    /// The Unicode normalization form the file was found in, if its name had to be normalized.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalization: Option<String>,
+}
+
+/// Writes records to stdout as a JSON array or as one JSON object per line.
+pub struct RecordWriter {
+    format: OutputFormat,
+    written: usize,
+}
+
+impl RecordWriter {
+    pub fn new(format: OutputFormat) -> Self {
+        Self { format, written: 0 }
+    }
+
+    pub fn is_text(&self) -> bool {
+        self.format == OutputFormat::Text
+    }
+
+    pub fn write(&mut self, record: &impl Serialize) -> anyhow::Result<()> {
+        let json = serde_json::to_string(record)?;
+        match self.format {
+            OutputFormat::Text => unreachable!("records are not written in text mode"),
+            OutputFormat::Json if self.written == 0 => print!("[\n{json}"),
+            OutputFormat::Json => print!(",\n{json}"),
+            OutputFormat::Ndjson => println!("{json}"),
+        }
+        self.written += 1;
+
+        Ok(())
+    }
+
+    pub fn finish(self) {
+        if self.format == OutputFormat::Json {
+            if self.written == 0 {
+                println!("[]");
+            } else {
+                println!("\n]");
+            }
+        }
+    }
+}
+
+/// Parses manifest contents written with `--output-format json` or `ndjson`, or returns `None`
+/// if `contents` isn't JSON records, like an SFV manifest for `[Group] file.mkv`.
+pub fn parse_records(contents: &str) -> Option<Vec<Result<ChecksumRecord, serde_json::Error>>> {
+    let trimmed = contents.trim_start();
+    if let Some(rest) = trimmed.strip_prefix('[')
+        && rest.trim_start().starts_with(['{', ']'])
+    {
+        let records = serde_json::from_str::<Vec<ChecksumRecord>>(trimmed).ok()?;
+        Some(records.into_iter().map(Ok).collect())
+    } else if trimmed.starts_with('{') {
+        let mut lines = trimmed
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .peekable();
+        serde_json::from_str::<ChecksumRecord>(lines.peek()?).ok()?;
+        Some(lines.map(serde_json::from_str).collect())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_records() {
+        let record = r#"{"path":"a","algorithm":"SHA256","digest":"00","size":1,"error":null}"#;
+        let records = parse_records(&format!("[\n{record},\n{record}\n]")).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].as_ref().unwrap().path, "a");
+
+        let records = parse_records(&format!("{record}\n\n{record}\n")).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].as_ref().unwrap().digest.as_deref(), Some("00"));
+
+        assert!(parse_records("00  a\n").is_none());
+        assert!(parse_records("[Group] Show 01.mkv 1234ABCD\n").is_none());
+        assert!(parse_records("{a}.mkv 1234ABCD\n").is_none());
+    }
+}
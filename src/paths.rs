@@ -0,0 +1,216 @@
+//! Mapping file names in manifests to files on disk.
+
+use crate::Options;
+use std::path::{Component, Path, PathBuf};
+use unicode_normalization::UnicodeNormalization;
+
+/// A `--replace-prefix OLD=NEW` argument.
+#[derive(Debug, Clone)]
+pub struct PrefixReplacement {
+    pub old: String,
+    pub new: String,
+}
+
+impl std::str::FromStr for PrefixReplacement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (old, new) = s
+            .split_once('=')
+            .ok_or_else(|| "expected OLD=NEW".to_string())?;
+        Ok(Self {
+            old: old.to_string(),
+            new: new.to_string(),
+        })
+    }
+}
+
+/// A `--normalize-names` form.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, clap::ValueEnum, strum::Display)]
+#[strum(serialize_all = "UPPERCASE")]
+pub enum Normalization {
+    #[clap(name = "nfc")]
+    Nfc,
+    #[clap(name = "nfd")]
+    Nfd,
+    #[clap(name = "auto")]
+    Auto,
+}
+
+impl Normalization {
+    /// The forms to retry with.
+    fn forms(self) -> &'static [Normalization] {
+        match self {
+            Normalization::Nfc => &[Normalization::Nfc],
+            Normalization::Nfd => &[Normalization::Nfd],
+            Normalization::Auto => &[Normalization::Nfc, Normalization::Nfd],
+        }
+    }
+
+    fn apply(self, path: &str) -> String {
+        match self {
+            Normalization::Nfc => path.nfc().collect(),
+            Normalization::Nfd => path.nfd().collect(),
+            Normalization::Auto => unreachable!("auto is not a form"),
+        }
+    }
+}
+
+fn convert_separators(path: &str) -> String {
+    if cfg!(windows) {
+        path.replace('/', "\\")
+    } else {
+        path.replace('\\', "/")
+    }
+}
+
+/// Strips `prefix` from `s`, comparing case-insensitively if `ignore_case` is set.
+fn strip_prefix<'a>(s: &'a str, prefix: &str, ignore_case: bool) -> Option<&'a str> {
+    if !ignore_case {
+        return s.strip_prefix(prefix);
+    }
+
+    let mut chars = s.chars();
+    for expected in prefix.chars() {
+        let actual = chars.next()?;
+        if !actual.to_lowercase().eq(expected.to_lowercase()) {
+            return None;
+        }
+    }
+    Some(chars.as_str())
+}
+
+/// Applies `--convert-separators`, `--strip-prefix` and `--replace-prefix` to a file name from a
+/// manifest. The first matching prefix is used.
+pub fn rewrite(options: &Options, filename: &str) -> String {
+    let convert = |path: &str| {
+        if options.convert_separators {
+            convert_separators(path)
+        } else {
+            path.to_string()
+        }
+    };
+
+    let filename = convert(filename);
+    for prefix in &options.strip_prefix {
+        if let Some(rest) = strip_prefix(&filename, &convert(prefix), options.ignore_case) {
+            return rest.to_string();
+        }
+    }
+    for replacement in &options.replace_prefix {
+        if let Some(rest) = strip_prefix(&filename, &convert(&replacement.old), options.ignore_case)
+        {
+            return convert(&replacement.new) + rest;
+        }
+    }
+
+    filename
+}
+
+/// Finds `path` on disk, matching each component case-insensitively if it doesn't exist as is.
+fn find_ignore_case(path: &Path) -> Option<PathBuf> {
+    if path.symlink_metadata().is_ok() {
+        return Some(path.to_path_buf());
+    }
+
+    let mut found = PathBuf::new();
+    for component in path.components() {
+        let Component::Normal(name) = component else {
+            found.push(component);
+            continue;
+        };
+        let candidate = found.join(name);
+        if candidate.symlink_metadata().is_ok() {
+            found = candidate;
+            continue;
+        }
+        let name = name.to_string_lossy().to_lowercase();
+        let dir = if found.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            found.as_path()
+        };
+        let entry = std::fs::read_dir(dir)
+            .ok()?
+            .flatten()
+            .find(|entry| entry.file_name().to_string_lossy().to_lowercase() == name)?;
+        found.push(entry.file_name());
+    }
+
+    Some(found)
+}
+
+/// Returns the path of the file named `filename` in `manifest`, and the Unicode normalization form
+/// it was found in if its name had to be normalized.
+pub fn resolve(
+    options: &Options,
+    manifest: &str,
+    filename: &str,
+) -> (String, Option<Normalization>) {
+    let join = |filename: &str| match options.base_directory(manifest) {
+        Some(base) if Path::new(filename).is_relative() => base.join(filename),
+        _ => PathBuf::from(filename),
+    };
+    let filename = rewrite(options, filename);
+    let mut path = join(&filename);
+    // Names from Windows may use backslashes as separators.
+    if !cfg!(windows) && filename.contains('\\') && path.symlink_metadata().is_err() {
+        let converted = join(&convert_separators(&filename));
+        if converted.symlink_metadata().is_ok() {
+            path = converted;
+        }
+    }
+    let path = if options.ignore_case {
+        find_ignore_case(&path).unwrap_or(path)
+    } else {
+        path
+    };
+
+    let path = path.to_string_lossy().into_owned();
+
+    if let Some(normalization) = options.normalize_names
+        && Path::new(&path).symlink_metadata().is_err()
+    {
+        for &form in normalization.forms() {
+            let normalized = form.apply(&path);
+            if normalized != path && Path::new(&normalized).symlink_metadata().is_ok() {
+                return (normalized, Some(form));
+            }
+        }
+    }
+
+    (path, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_prefix() {
+        assert_eq!(
+            strip_prefix("/build/out/a", "/build/out/", false),
+            Some("a")
+        );
+        assert_eq!(strip_prefix("C:/Release/a", "c:/release/", false), None);
+        assert_eq!(strip_prefix("C:/Release/a", "c:/release/", true), Some("a"));
+        assert_eq!(strip_prefix("C:/", "c:/release/", true), None);
+    }
+
+    #[test]
+    fn test_find_ignore_case() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        assert_eq!(
+            find_ignore_case(&dir.join("DEMO/Foo/01.TXT")),
+            Some(dir.join("demo/foo/01.txt"))
+        );
+        assert_eq!(find_ignore_case(&dir.join("demo/foo/04.txt")), None);
+    }
+
+    #[test]
+    fn test_normalization() {
+        assert_eq!(Normalization::Nfd.apply("caf\u{e9}"), "cafe\u{301}");
+        assert_eq!(Normalization::Nfc.apply("cafe\u{301}"), "caf\u{e9}");
+        assert_eq!(Normalization::Nfd.to_string(), "NFD");
+    }
+}
@@ -0,0 +1,104 @@
+//! OpenPGP cleartext signed messages (RFC 4880 section 7), as used for `SHA256SUMS.asc` or
+//! `CHECKSUM` files. Only the framing is handled; the signature itself is left to gpg.
+
+const BEGIN_MESSAGE: &str = "-----BEGIN PGP SIGNED MESSAGE-----";
+const BEGIN_SIGNATURE: &str = "-----BEGIN PGP SIGNATURE-----";
+const END_SIGNATURE: &str = "-----END PGP SIGNATURE-----";
+
+pub struct Cleartext {
+    /// The signed text, with dash-escaping removed. The line break before the signature isn't
+    /// part of it, so that `gpg --verify` accepts it with the detached `signature`.
+    pub body: String,
+    /// The line number of the first line of `body` in the file.
+    pub first_line: usize,
+    /// The armored signature block, including its BEGIN and END lines.
+    pub signature: String,
+    /// The number of non-blank lines before or after the signed message, which aren't covered by
+    /// the signature.
+    pub unsigned_lines: usize,
+}
+
+impl Cleartext {
+    /// Parses a cleartext signed message. Returns `None` if `contents` has no
+    /// `-----BEGIN PGP SIGNED MESSAGE-----` line.
+    pub fn parse(contents: &str) -> Option<anyhow::Result<Cleartext>> {
+        let lines = contents.lines().collect::<Vec<_>>();
+        let begin = lines.iter().position(|&line| line == BEGIN_MESSAGE)?;
+
+        Some(Self::parse_message(&lines, begin))
+    }
+
+    fn parse_message(lines: &[&str], begin: usize) -> anyhow::Result<Cleartext> {
+        // Armor headers like `Hash: SHA256`, ended by a blank line.
+        let Some(headers) = lines[begin + 1..]
+            .iter()
+            .position(|line| line.trim().is_empty())
+        else {
+            anyhow::bail!("missing blank line after the PGP armor headers");
+        };
+        let body_start = begin + 1 + headers + 1;
+        let Some(signature_start) = lines[body_start..]
+            .iter()
+            .position(|&line| line == BEGIN_SIGNATURE)
+            .map(|index| body_start + index)
+        else {
+            anyhow::bail!("missing PGP signature");
+        };
+        let Some(signature_end) = lines[signature_start..]
+            .iter()
+            .position(|&line| line == END_SIGNATURE)
+            .map(|index| signature_start + index)
+        else {
+            anyhow::bail!("unterminated PGP signature");
+        };
+
+        let body = lines[body_start..signature_start]
+            .iter()
+            .map(|line| line.strip_prefix("- ").unwrap_or(line))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let signature = lines[signature_start..=signature_end]
+            .iter()
+            .fold(String::new(), |signature, line| signature + line + "\n");
+        let unsigned_lines = lines[..begin]
+            .iter()
+            .chain(&lines[signature_end + 1..])
+            .filter(|line| !line.trim().is_empty())
+            .count();
+
+        Ok(Cleartext {
+            body,
+            first_line: body_start + 1,
+            signature,
+            unsigned_lines,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let message = Cleartext::parse(
+            "unsigned\n-----BEGIN PGP SIGNED MESSAGE-----\nHash: SHA256\n\nabc  file\n- --- not a header\n-----BEGIN PGP SIGNATURE-----\n\niQ==\n=abcd\n-----END PGP SIGNATURE-----\n\n",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(message.body, "abc  file\n--- not a header");
+        assert_eq!(message.first_line, 5);
+        assert_eq!(
+            message.signature,
+            "-----BEGIN PGP SIGNATURE-----\n\niQ==\n=abcd\n-----END PGP SIGNATURE-----\n"
+        );
+        assert_eq!(message.unsigned_lines, 1);
+
+        assert!(Cleartext::parse("abc  file\n").is_none());
+        assert!(
+            Cleartext::parse("-----BEGIN PGP SIGNED MESSAGE-----\nHash: SHA256\n\nabc  file\n")
+                .unwrap()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,308 @@
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum ReportKind {
+    Junit,
+    Tap,
+}
+
+/// A report requested with `--report KIND[=PATH]`. Reports without a path go to stdout, where
+/// they replace the OK lines of check mode.
+#[derive(Debug, Clone)]
+pub struct ReportTarget {
+    pub kind: ReportKind,
+    pub path: Option<String>,
+}
+
+impl std::str::FromStr for ReportTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, path) = match s.split_once('=') {
+            Some((kind, path)) => (kind, Some(path.to_string())),
+            None => (s, None),
+        };
+        let kind = kind
+            .parse()
+            .map_err(|_| format!("unknown report kind `{kind}`, expected junit or tap"))?;
+        Ok(Self { kind, path })
+    }
+}
+
+#[derive(Debug)]
+pub enum Outcome {
+    Passed,
+    Failed {
+        expected: String,
+        actual: String,
+    },
+    Error {
+        class: &'static str,
+        message: String,
+    },
+    Skipped {
+        message: String,
+    },
+}
+
+#[derive(Debug)]
+pub struct TestCase {
+    pub manifest: String,
+    pub name: String,
+    pub outcome: Outcome,
+    /// The Unicode normalization form the file was found in, if its name had to be normalized.
+    pub normalization: Option<String>,
+}
+
+/// Collects one test case per manifest line in check mode.
+#[derive(Debug, Default)]
+pub struct Reporter {
+    cases: Vec<TestCase>,
+    manifest: String,
+    line_number: usize,
+}
+
+impl Reporter {
+    /// Sets the location used to name test cases for lines without a file name.
+    pub fn start_line(&mut self, manifest: &str, line_number: usize) {
+        self.manifest = manifest.to_string();
+        self.line_number = line_number;
+    }
+
+    pub fn record(&mut self, name: &str, normalization: Option<String>, outcome: Outcome) {
+        self.cases.push(TestCase {
+            manifest: self.manifest.clone(),
+            name: name.to_string(),
+            outcome,
+            normalization,
+        });
+    }
+
+    pub fn record_invalid(&mut self, message: String) {
+        let name = format!("{}:{}", self.manifest, self.line_number);
+        self.record(
+            &name,
+            None,
+            Outcome::Error {
+                class: "invalid_line",
+                message,
+            },
+        );
+    }
+
+    pub fn write(&self, target: &ReportTarget) -> anyhow::Result<()> {
+        let report = match target.kind {
+            ReportKind::Junit => self.to_junit(),
+            ReportKind::Tap => self.to_tap(),
+        };
+        match &target.path {
+            Some(path) => fs_err::write(path, report)?,
+            None => print!("{report}"),
+        }
+
+        Ok(())
+    }
+
+    fn to_junit(&self) -> String {
+        let mut manifests: Vec<&str> = vec![];
+        for case in &self.cases {
+            if !manifests.contains(&case.manifest.as_str()) {
+                manifests.push(&case.manifest);
+            }
+        }
+
+        let mut out = String::new();
+        let (failures, errors, skipped) = count_outcomes(self.cases.iter());
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+        writeln!(
+            out,
+            r#"<testsuites name="ccsum" tests="{}" failures="{failures}" errors="{errors}" skipped="{skipped}">"#,
+            self.cases.len()
+        )
+        .unwrap();
+        for manifest in manifests {
+            let cases = self
+                .cases
+                .iter()
+                .filter(|case| case.manifest == manifest)
+                .collect::<Vec<_>>();
+            let (failures, errors, skipped) = count_outcomes(cases.iter().copied());
+            writeln!(
+                out,
+                r#"  <testsuite name="{}" tests="{}" failures="{failures}" errors="{errors}" skipped="{skipped}">"#,
+                escape_xml(manifest),
+                cases.len()
+            )
+            .unwrap();
+            for case in cases {
+                let open = format!(
+                    r#"    <testcase classname="{}" name="{}""#,
+                    escape_xml(manifest),
+                    escape_xml(&case.name)
+                );
+                let properties = match &case.normalization {
+                    Some(form) => format!(
+                        "\n      <properties>\n        <property name=\"normalization\" value=\"{form}\"/>\n      </properties>"
+                    ),
+                    None => String::new(),
+                };
+                match &case.outcome {
+                    Outcome::Passed if properties.is_empty() => writeln!(out, "{open}/>").unwrap(),
+                    Outcome::Passed => writeln!(out, "{open}>{properties}\n    </testcase>").unwrap(),
+                    Outcome::Failed { expected, actual } => writeln!(
+                        out,
+                        "{open}>{properties}\n      <failure type=\"checksum_mismatch\" message=\"checksum mismatch\">expected: {expected}\nactual: {actual}</failure>\n    </testcase>"
+                    )
+                    .unwrap(),
+                    Outcome::Error { class, message } => writeln!(
+                        out,
+                        "{open}>{properties}\n      <error type=\"{class}\" message=\"{}\"/>\n    </testcase>",
+                        escape_xml(message)
+                    )
+                    .unwrap(),
+                    Outcome::Skipped { message } => writeln!(
+                        out,
+                        "{open}>{properties}\n      <skipped message=\"{}\"/>\n    </testcase>",
+                        escape_xml(message)
+                    )
+                    .unwrap(),
+                }
+            }
+            writeln!(out, "  </testsuite>").unwrap();
+        }
+        writeln!(out, "</testsuites>").unwrap();
+
+        out
+    }
+
+    fn to_tap(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "TAP version 13").unwrap();
+        writeln!(out, "1..{}", self.cases.len()).unwrap();
+        for (index, case) in self.cases.iter().enumerate() {
+            let number = index + 1;
+            let name = escape_tap(&case.name);
+            let normalization = case
+                .normalization
+                .as_ref()
+                .map(|form| format!("  normalization: {form}\n"))
+                .unwrap_or_default();
+            match &case.outcome {
+                Outcome::Passed if normalization.is_empty() => {
+                    writeln!(out, "ok {number} - {name}").unwrap()
+                }
+                Outcome::Passed => {
+                    writeln!(out, "ok {number} - {name}\n  ---\n{normalization}  ...").unwrap()
+                }
+                Outcome::Failed { expected, actual } => writeln!(
+                    out,
+                    "not ok {number} - {name}\n  ---\n  message: checksum mismatch\n  severity: fail\n  expected: {expected}\n  actual: {actual}\n{normalization}  ..."
+                )
+                .unwrap(),
+                Outcome::Error { class, message } => writeln!(
+                    out,
+                    "not ok {number} - {name}\n  ---\n  message: {}\n  severity: error\n  class: {class}\n{normalization}  ...",
+                    serde_json::to_string(message).unwrap()
+                )
+                .unwrap(),
+                Outcome::Skipped { message } => {
+                    writeln!(out, "ok {number} - {name} # SKIP {}", escape_tap(message)).unwrap()
+                }
+            }
+        }
+
+        out
+    }
+}
+
+fn count_outcomes<'a>(cases: impl Iterator<Item = &'a TestCase>) -> (usize, usize, usize) {
+    let (mut failures, mut errors, mut skipped) = (0, 0, 0);
+    for case in cases {
+        match case.outcome {
+            Outcome::Passed => {}
+            Outcome::Failed { .. } => failures += 1,
+            Outcome::Error { .. } => errors += 1,
+            Outcome::Skipped { .. } => skipped += 1,
+        }
+    }
+
+    (failures, errors, skipped)
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            '\t' => escaped.push_str("&#9;"),
+            // Other control characters are not allowed in XML 1.0, even as references.
+            c if c.is_control() => escaped.push(char::REPLACEMENT_CHARACTER),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Escapes characters that TAP parsers would treat as directives or line breaks.
+fn escape_tap(s: &str) -> String {
+    crate::escape::escape(s).replace('#', "\\#")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reporter() -> Reporter {
+        let mut reporter = Reporter::default();
+        reporter.start_line("SUMS", 1);
+        reporter.record("a.bin", None, Outcome::Passed);
+        reporter.start_line("SUMS", 2);
+        reporter.record(
+            "b&c.bin",
+            None,
+            Outcome::Failed {
+                expected: "00".to_string(),
+                actual: "11".to_string(),
+            },
+        );
+        reporter.start_line("SUMS", 3);
+        reporter.record_invalid("pattern not matched".to_string());
+        reporter.start_line("SUMS", 4);
+        reporter.record("cafe\u{301}.bin", Some("NFD".to_string()), Outcome::Passed);
+        reporter
+    }
+
+    #[test]
+    fn test_junit() {
+        let junit = reporter().to_junit();
+        assert!(
+            junit.contains(
+                r#"<testsuite name="SUMS" tests="4" failures="1" errors="1" skipped="0">"#
+            )
+        );
+        assert!(junit.contains(r#"<testcase classname="SUMS" name="a.bin"/>"#));
+        assert!(junit.contains(r#"name="b&amp;c.bin""#));
+        assert!(junit.contains("expected: 00\nactual: 11</failure>"));
+        assert!(junit.contains(r#"<testcase classname="SUMS" name="SUMS:3">"#));
+        assert!(junit.contains(
+            r#"<property name="normalization" value="NFD"/>
+      </properties>
+    </testcase>"#
+        ));
+    }
+
+    #[test]
+    fn test_tap() {
+        assert_eq!(
+            reporter().to_tap(),
+            "TAP version 13\n1..4\nok 1 - a.bin\nnot ok 2 - b&c.bin\n  ---\n  message: checksum mismatch\n  severity: fail\n  expected: 00\n  actual: 11\n  ...\nnot ok 3 - SUMS:3\n  ---\n  message: \"pattern not matched\"\n  severity: error\n  class: invalid_line\n  ...\nok 4 - cafe\u{301}.bin\n  ---\n  normalization: NFD\n  ...\n"
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use crate::{Algorithm, CheckError, Options, checksum_file, output, report, utils};
+use colored::Colorize;
+use strum::IntoEnumIterator;
+
+#[derive(Debug, Copy, Clone, clap::ValueEnum)]
+pub enum SidecarStyle {
+    // `<digest>  <file name>`, as written by sha256sum.
+    #[clap(name = "gnu")]
+    Gnu,
+    // The digest alone.
+    #[clap(name = "bare")]
+    Bare,
+}
+
+/// Writes `<file>.<algorithm>` next to each input file.
+pub fn do_sidecar(options: &Options, style: SidecarStyle) -> anyhow::Result<()> {
+    let mut anything_failed = false;
+    let extension = format!(".{}", options.algorithm.prefix());
+    for file in utils::expand_paths(&options.files)? {
+        if file == "-" {
+            eprintln!("{}: {}", file, "stdin is not supported here".red());
+            anything_failed = true;
+            continue;
+        }
+        // Don't write checksums of the sidecars themselves when run again on the same tree, with
+        // any algorithm.
+        if sidecar_target(&file).is_some() {
+            continue;
+        }
+
+        let result =
+            checksum_file(&file, options.algorithm, options.buffer_size).and_then(|checksum| {
+                let digest = options.format.encode(options.algorithm, &checksum);
+                let contents = match style {
+                    SidecarStyle::Gnu => {
+                        let name = std::path::Path::new(&file)
+                            .file_name()
+                            .map(|name| name.to_string_lossy().into_owned())
+                            .unwrap_or_default();
+                        let escaped = crate::escape::escape(&name);
+                        let marker = if escaped != name { "\\" } else { "" };
+                        format!("{marker}{digest}  {escaped}\n")
+                    }
+                    SidecarStyle::Bare => format!("{digest}\n"),
+                };
+                let sidecar = format!("{file}{extension}");
+                fs_err::write(&sidecar, contents)?;
+                Ok(sidecar)
+            });
+        match result {
+            Ok(sidecar) => {
+                if !options.quiet {
+                    println!("{}: {}", sidecar, "written".green());
+                }
+            }
+            Err(e) => {
+                eprintln!("{}: {}", file, e.to_string().red());
+                anything_failed = true;
+            }
+        }
+    }
+
+    if anything_failed {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Returns the algorithm named by the extension of a sidecar file, and the path of its target.
+fn sidecar_target(path: &str) -> Option<(Algorithm, &str)> {
+    let (target, extension) = path.rsplit_once('.')?;
+    let algorithm = Algorithm::iter().find(|algorithm| algorithm.prefix() == extension)?;
+    Some((algorithm, target))
+}
+
+/// Verifies every sidecar file found in the given paths against the file next to it.
+pub fn do_check_sidecars(options: &Options) -> anyhow::Result<()> {
+    let mut anything_succeeded = false;
+    let mut anything_failed = false;
+    let mut writer = output::RecordWriter::new(options.output_format);
+    let mut reporter = report::Reporter::default();
+    for sidecar in utils::expand_paths(&options.files)? {
+        let Some((algorithm, target)) = sidecar_target(&sidecar) else {
+            continue;
+        };
+        reporter.start_line(&sidecar, 1);
+        let contents = fs_err::read(&sidecar)
+            .map_err(anyhow::Error::from)
+            .and_then(utils::decode_text);
+        let contents = match contents {
+            Ok(contents) => contents,
+            Err(e) => {
+                eprintln!("{}: {}", sidecar, e.to_string().red());
+                anything_failed = true;
+                continue;
+            }
+        };
+        let line = contents.lines().find(|line| !line.trim().is_empty());
+        let hash = match line.map(|line| crate::parse_line(line, algorithm)) {
+            Some(Ok((_, _, hash))) => hash,
+            // A bare digest, possibly followed by something other than a GNU file name.
+            Some(Err(_)) => line
+                .and_then(|line| line.split_whitespace().next())
+                .unwrap_or_default()
+                .to_string(),
+            None => {
+                let error = CheckError::InvalidLine("empty sidecar file".to_string());
+                reporter.record_invalid(error.to_string());
+                if crate::do_invalid_line(options, &mut writer, &sidecar, error)? == Some(false) {
+                    anything_failed = true;
+                }
+                continue;
+            }
+        };
+        match crate::do_entry(
+            options,
+            &mut writer,
+            &mut reporter,
+            &sidecar,
+            algorithm,
+            target,
+            &hash,
+        )? {
+            Some(true) => anything_succeeded = true,
+            Some(false) => anything_failed = true,
+            None => {}
+        }
+    }
+
+    crate::finish_check(
+        options,
+        writer,
+        &reporter,
+        anything_succeeded,
+        anything_failed,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sidecar_target() {
+        assert_eq!(
+            sidecar_target("dir/file.tar.gz.sha256"),
+            Some((Algorithm::SHA256, "dir/file.tar.gz"))
+        );
+        assert_eq!(sidecar_target("file.md5"), Some((Algorithm::MD5, "file")));
+        assert_eq!(sidecar_target("file.txt"), None);
+    }
+}
@@ -0,0 +1,391 @@
+//! Ed25519 signatures of OpenBSD signify (`file.sig`) and minisign (`file.minisig`) over
+//! manifests. Both share the public key format: a comment line, then base64 of `Ed`, an 8 byte key
+//! ID and the key.
+
+use crate::{Options, sshsig, utils};
+use base64::Engine;
+use blake2::Digest;
+use colored::Colorize;
+use ed25519_dalek::{Signer, SigningKey, VerifyingKey};
+use sha2::Sha512;
+
+type Blake2b256 = blake2::Blake2b<blake2::digest::consts::U32>;
+
+const UNTRUSTED_COMMENT: &str = "untrusted comment: ";
+const TRUSTED_COMMENT: &str = "trusted comment: ";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Flavor {
+    Signify,
+    Minisign,
+}
+
+impl Flavor {
+    fn extension(self) -> &'static str {
+        match self {
+            Flavor::Signify => "sig",
+            Flavor::Minisign => "minisig",
+        }
+    }
+}
+
+/// Decodes the base64 line following the untrusted comment of a key or signature file.
+fn decode_payload(contents: &str) -> anyhow::Result<Vec<u8>> {
+    let line = contents
+        .lines()
+        .find(|line| !line.starts_with(UNTRUSTED_COMMENT) && !line.trim().is_empty())
+        .ok_or_else(|| anyhow::anyhow!("missing key or signature"))?;
+    Ok(base64::engine::general_purpose::STANDARD.decode(line.trim())?)
+}
+
+/// Whether `contents` is a signify or minisign signature rather than an SSH one.
+pub fn is_signature(contents: &str) -> bool {
+    contents.starts_with(UNTRUSTED_COMMENT)
+}
+
+pub struct PublicKey {
+    key_id: [u8; 8],
+    key: VerifyingKey,
+}
+
+impl PublicKey {
+    pub fn read(path: &str) -> anyhow::Result<PublicKey> {
+        Self::decode(&fs_err::read_to_string(path)?).map_err(|e| anyhow::anyhow!("{path}: {e}"))
+    }
+
+    fn decode(contents: &str) -> anyhow::Result<PublicKey> {
+        let payload = decode_payload(contents)?;
+        let (Some(b"Ed"), Ok(key_id), Ok(key)) = (
+            payload.get(..2),
+            payload.get(2..10).unwrap_or_default().try_into(),
+            payload.get(10..).unwrap_or_default().try_into(),
+        ) else {
+            anyhow::bail!("not an Ed25519 signify or minisign public key");
+        };
+
+        Ok(PublicKey {
+            key_id,
+            key: VerifyingKey::from_bytes(key)
+                .map_err(|e| anyhow::anyhow!("invalid public key: {e}"))?,
+        })
+    }
+
+    fn verify_ed25519(&self, message: &[u8], signature: &[u8]) -> anyhow::Result<()> {
+        let signature = ed25519_dalek::Signature::from_slice(signature)?;
+        self.key
+            .verify_strict(message, &signature)
+            .map_err(|_| anyhow::anyhow!("invalid signature"))
+    }
+
+    /// Verifies the contents of a signify or minisign signature file over `message`.
+    pub fn verify(&self, message: &[u8], signature: &str) -> anyhow::Result<()> {
+        let payload = decode_payload(signature)?;
+        if payload.len() != 74 {
+            anyhow::bail!("not an Ed25519 signature");
+        }
+        let (algorithm, key_id, signature_bytes) = (&payload[..2], &payload[2..10], &payload[10..]);
+        if key_id != self.key_id {
+            anyhow::bail!("signed with another key");
+        }
+
+        let trusted_comment = signature
+            .lines()
+            .find_map(|line| line.strip_prefix(TRUSTED_COMMENT));
+        let Some(trusted_comment) = trusted_comment else {
+            // signify signs the message itself.
+            if algorithm != b"Ed" {
+                anyhow::bail!("unsupported signature algorithm");
+            }
+            return self.verify_ed25519(message, signature_bytes);
+        };
+
+        // minisign signs the message, or its BLAKE2b-512 hash with `ED`, and then the signature
+        // with the trusted comment.
+        let prehashed;
+        let message = match algorithm {
+            b"Ed" => message,
+            b"ED" => {
+                prehashed = blake2::Blake2b512::digest(message);
+                prehashed.as_slice()
+            }
+            _ => anyhow::bail!("unsupported signature algorithm"),
+        };
+        self.verify_ed25519(message, signature_bytes)?;
+        let global_signature = signature
+            .lines()
+            .skip_while(|line| !line.starts_with(TRUSTED_COMMENT))
+            .nth(1)
+            .ok_or_else(|| anyhow::anyhow!("missing global signature"))?;
+        let global_signature =
+            base64::engine::general_purpose::STANDARD.decode(global_signature.trim())?;
+        self.verify_ed25519(
+            &[signature_bytes, trusted_comment.as_bytes()].concat(),
+            &global_signature,
+        )
+        .map_err(|_| anyhow::anyhow!("invalid signature of the trusted comment"))
+    }
+}
+
+/// Verifies `contents` of `manifest` against `manifest.minisig` or `manifest.sig`.
+pub fn verify_manifest(
+    public_key: &PublicKey,
+    manifest: &str,
+    contents: &[u8],
+) -> anyhow::Result<()> {
+    if manifest == "-" {
+        anyhow::bail!("-: signatures can only be verified for manifest files");
+    }
+    let signature_path = [Flavor::Minisign, Flavor::Signify]
+        .map(|flavor| format!("{manifest}.{}", flavor.extension()))
+        .into_iter()
+        .find(|path| std::path::Path::new(path).exists())
+        .ok_or_else(|| anyhow::anyhow!("{manifest}: no .minisig or .sig signature found"))?;
+    let signature = fs_err::read_to_string(&signature_path)?;
+    if sshsig::is_signature(&signature) {
+        anyhow::bail!(
+            "{signature_path}: an SSH signature, verify it with --allowed-signers instead"
+        );
+    }
+    public_key
+        .verify(contents, &signature)
+        .map_err(|e| anyhow::anyhow!("{signature_path}: {e}"))
+}
+
+pub struct SecretKey {
+    flavor: Flavor,
+    key_id: [u8; 8],
+    key: SigningKey,
+}
+
+impl SecretKey {
+    /// Decodes an unencrypted signify (`signify -G -n`) or minisign (`minisign -G -W`) secret
+    /// key.
+    fn decode(payload: &[u8]) -> anyhow::Result<SecretKey> {
+        match (payload.len(), payload.get(..2), payload.get(2..4)) {
+            // `Ed`, `BK`, KDF rounds, salt, checksum, key ID, seed and public key.
+            (104, Some(b"Ed"), Some(b"BK")) => {
+                if payload[4..8] != [0; 4] {
+                    anyhow::bail!("encrypted secret keys are not supported; use `signify -G -n`");
+                }
+                let (checksum, key_id, secret) =
+                    (&payload[24..32], &payload[32..40], &payload[40..104]);
+                if Sha512::digest(secret)[..8] != *checksum {
+                    anyhow::bail!("secret key checksum mismatch");
+                }
+                Self::new(Flavor::Signify, key_id, secret)
+            }
+            // `Ed`, KDF, `B2`, KDF salt and limits, key ID, seed, public key and checksum.
+            (158, Some(b"Ed"), Some(kdf)) if payload[4..6] == *b"B2" => {
+                if kdf != [0, 0] {
+                    anyhow::bail!("encrypted secret keys are not supported; use `minisign -G -W`");
+                }
+                let (key_id, secret, checksum) =
+                    (&payload[54..62], &payload[62..126], &payload[126..158]);
+                if Blake2b256::digest([b"Ed", key_id, secret].concat())[..] != *checksum {
+                    anyhow::bail!("secret key checksum mismatch");
+                }
+                Self::new(Flavor::Minisign, key_id, secret)
+            }
+            _ => anyhow::bail!("not an Ed25519 signify or minisign secret key"),
+        }
+    }
+
+    fn new(flavor: Flavor, key_id: &[u8], secret: &[u8]) -> anyhow::Result<SecretKey> {
+        let key = SigningKey::from_keypair_bytes(secret.try_into()?)
+            .map_err(|e| anyhow::anyhow!("invalid secret key: {e}"))?;
+        Ok(SecretKey {
+            flavor,
+            key_id: key_id.try_into()?,
+            key,
+        })
+    }
+
+    /// Returns the contents of a signature file over `message`, which is the contents of
+    /// `file_name`.
+    fn sign(&self, message: &[u8], file_name: &str) -> String {
+        let base64 = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+        match self.flavor {
+            Flavor::Signify => {
+                let signature = self.key.sign(message).to_bytes();
+                format!(
+                    "{UNTRUSTED_COMMENT}verify with signify public key\n{}\n",
+                    base64(&[b"Ed", &self.key_id[..], &signature].concat())
+                )
+            }
+            Flavor::Minisign => {
+                let signature = self
+                    .key
+                    .sign(&blake2::Blake2b512::digest(message))
+                    .to_bytes();
+                let timestamp = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|duration| duration.as_secs())
+                    .unwrap_or_default();
+                let trusted_comment = format!("timestamp:{timestamp}\tfile:{file_name}\thashed");
+                let global_signature = self
+                    .key
+                    .sign(&[&signature[..], trusted_comment.as_bytes()].concat())
+                    .to_bytes();
+                format!(
+                    "{UNTRUSTED_COMMENT}signature from minisign secret key\n{}\n{TRUSTED_COMMENT}{trusted_comment}\n{}\n",
+                    base64(&[b"ED", &self.key_id[..], &signature].concat()),
+                    base64(&global_signature)
+                )
+            }
+        }
+    }
+}
+
+/// A key to sign manifests with.
+enum ManifestSigner {
+    Ed25519(SecretKey),
+    Ssh(ssh_key::PrivateKey),
+}
+
+impl ManifestSigner {
+    fn read(path: &str) -> anyhow::Result<ManifestSigner> {
+        let contents = fs_err::read_to_string(path)?;
+        let signer = if sshsig::is_private_key(&contents) {
+            sshsig::parse_private_key(&contents).map(ManifestSigner::Ssh)
+        } else {
+            decode_payload(&contents)
+                .and_then(|payload| SecretKey::decode(&payload))
+                .map(ManifestSigner::Ed25519)
+        };
+        signer.map_err(|e| anyhow::anyhow!("{path}: {e}"))
+    }
+
+    /// Returns the extension and contents of a signature file over `message`, which is the
+    /// contents of `file_name`.
+    fn sign(
+        &self,
+        options: &Options,
+        message: &[u8],
+        file_name: &str,
+    ) -> anyhow::Result<(&'static str, String)> {
+        match self {
+            ManifestSigner::Ed25519(key) => {
+                Ok((key.flavor.extension(), key.sign(message, file_name)))
+            }
+            ManifestSigner::Ssh(key) => {
+                Ok(("sig", sshsig::sign(key, &options.namespace, message)?))
+            }
+        }
+    }
+}
+
+/// Signs the given manifests with a signify, minisign or SSH secret key, writing `file.sig` or
+/// `file.minisig` next to each.
+pub fn do_sign(options: &Options, key_path: &str) -> anyhow::Result<()> {
+    let signer = ManifestSigner::read(key_path)?;
+    let mut anything_failed = false;
+    for file in utils::expand_paths(&options.files)? {
+        if file == "-" {
+            eprintln!("{}: {}", file, "stdin is not supported here".red());
+            anything_failed = true;
+            continue;
+        }
+
+        let result = fs_err::read(&file)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| {
+                let file_name = std::path::Path::new(&file)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let (extension, signature) = signer.sign(options, &contents, &file_name)?;
+                let signature_path = format!("{file}.{extension}");
+                fs_err::write(&signature_path, signature)?;
+                Ok(signature_path)
+            });
+        match result {
+            Ok(signature_path) => {
+                if !options.quiet {
+                    println!("{}: {}", signature_path, "written".green());
+                }
+            }
+            Err(e) => {
+                eprintln!("{}: {}", file, e.to_string().red());
+                anything_failed = true;
+            }
+        }
+    }
+
+    if anything_failed {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_minisign() {
+        // From the minisign-verify crate.
+        let public_key = PublicKey::decode(
+            "untrusted comment: minisign public key E7620F1842B4E81F\n\
+             RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3\n",
+        )
+        .unwrap();
+        let signature = "untrusted comment: signature from minisign secret key\n\
+            RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=\n\
+            trusted comment: timestamp:1633700835\tfile:test\tprehashed\n\
+            wLMDjy9FLAuxZ3q4NlEvkgtyhrr0gtTu6KC4KBJdITbbOeAi1zBIYo0v4iTgt8jJpIidRJnp94ABQkJAgAooBQ==\n";
+        public_key.verify(b"test", signature).unwrap();
+        assert!(public_key.verify(b"tset", signature).is_err());
+        assert!(
+            public_key
+                .verify(b"test", &signature.replace("prehashed", "hashed"))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_sign() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let key_id = *b"12345678";
+        let public_key = PublicKey {
+            key_id,
+            key: key.verifying_key(),
+        };
+        for flavor in [Flavor::Signify, Flavor::Minisign] {
+            let secret_key = SecretKey {
+                flavor,
+                key_id,
+                key: key.clone(),
+            };
+            let signature = secret_key.sign(b"abc  file\n", "SHA256SUMS");
+            public_key.verify(b"abc  file\n", &signature).unwrap();
+            assert!(public_key.verify(b"abd  file\n", &signature).is_err());
+        }
+    }
+
+    #[test]
+    fn test_decode_secret_key() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let secret = key.to_keypair_bytes();
+        let mut signify = [b"Ed", b"BK", &[0; 4][..], &[0; 16]].concat();
+        signify.extend(&Sha512::digest(secret)[..8]);
+        signify.extend(b"12345678");
+        signify.extend(secret);
+        let decoded = SecretKey::decode(&signify).unwrap();
+        assert_eq!(decoded.flavor, Flavor::Signify);
+        assert_eq!(decoded.key_id, *b"12345678");
+
+        signify[7] = 16;
+        assert!(SecretKey::decode(&signify).is_err());
+
+        let mut minisign = [b"Ed", &[0, 0][..], b"B2", &[0; 48]].concat();
+        minisign.extend(b"12345678");
+        minisign.extend(secret);
+        minisign.extend(Blake2b256::digest(
+            [b"Ed", &b"12345678"[..], &secret].concat(),
+        ));
+        let decoded = SecretKey::decode(&minisign).unwrap();
+        assert_eq!(decoded.flavor, Flavor::Minisign);
+        assert_eq!(decoded.key.verifying_key(), key.verifying_key());
+    }
+}
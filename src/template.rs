@@ -0,0 +1,147 @@
+use crate::escape;
+
+/// An output template given with `--printf`.
+#[derive(Debug, Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Digest,
+    Path,
+    EscapedPath,
+    Size,
+    Mtime,
+    Algorithm,
+    Head,
+    Tail,
+}
+
+/// The values a template can refer to.
+pub struct Fields<'a> {
+    pub digest: &'a str,
+    pub path: &'a str,
+    pub algorithm: &'a str,
+    pub head: Option<&'a str>,
+    pub tail: &'a str,
+    pub metadata: Option<&'a std::fs::Metadata>,
+}
+
+impl std::str::FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = vec![];
+        let mut literal = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            let part = match c {
+                '%' => match chars.next() {
+                    Some('h') => Part::Digest,
+                    Some('f') => Part::Path,
+                    Some('e') => Part::EscapedPath,
+                    Some('s') => Part::Size,
+                    Some('t') => Part::Mtime,
+                    Some('a') => Part::Algorithm,
+                    Some('H') => Part::Head,
+                    Some('T') => Part::Tail,
+                    Some('%') => {
+                        literal.push('%');
+                        continue;
+                    }
+                    Some(c) => return Err(format!("unknown placeholder: %{c}")),
+                    None => return Err("incomplete placeholder".to_string()),
+                },
+                '\\' => {
+                    match chars.next() {
+                        Some('n') => literal.push('\n'),
+                        Some('t') => literal.push('\t'),
+                        Some('0') => literal.push('\0'),
+                        Some('\\') => literal.push('\\'),
+                        Some(c) => return Err(format!("invalid escape sequence: \\{c}")),
+                        None => return Err("incomplete escape sequence".to_string()),
+                    }
+                    continue;
+                }
+                c => {
+                    literal.push(c);
+                    continue;
+                }
+            };
+            if !literal.is_empty() {
+                parts.push(Part::Literal(std::mem::take(&mut literal)));
+            }
+            parts.push(part);
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Self { parts })
+    }
+}
+
+impl Template {
+    /// Whether rendering needs the file metadata.
+    pub fn needs_metadata(&self) -> bool {
+        self.parts
+            .iter()
+            .any(|part| matches!(part, Part::Size | Part::Mtime))
+    }
+
+    pub fn render(&self, fields: &Fields) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => out.push_str(literal),
+                Part::Digest => out.push_str(fields.digest),
+                Part::Path => out.push_str(fields.path),
+                Part::EscapedPath => out.push_str(&escape::escape(fields.path)),
+                Part::Size => match fields.metadata {
+                    Some(metadata) => out.push_str(&metadata.len().to_string()),
+                    None => out.push('-'),
+                },
+                Part::Mtime => match fields
+                    .metadata
+                    .and_then(|metadata| metadata.modified().ok())
+                    .and_then(|mtime| mtime.duration_since(std::time::UNIX_EPOCH).ok())
+                {
+                    Some(mtime) => out.push_str(&mtime.as_secs().to_string()),
+                    None => out.push('-'),
+                },
+                Part::Algorithm => out.push_str(fields.algorithm),
+                Part::Head => out.push_str(fields.head.unwrap_or_default()),
+                Part::Tail => out.push_str(fields.tail),
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let template = "%h  %e\\t%a %H|%T 100%%\\0".parse::<Template>().unwrap();
+        let fields = Fields {
+            digest: "abcd",
+            path: "dir/a\nb",
+            algorithm: "SHA256",
+            head: Some("dir/"),
+            tail: "a\nb",
+            metadata: None,
+        };
+        assert_eq!(
+            template.render(&fields),
+            "abcd  dir/a\\nb\tSHA256 dir/|a\nb 100%\0"
+        );
+        assert!(!template.needs_metadata());
+        assert!("%s".parse::<Template>().unwrap().needs_metadata());
+        assert!("%x".parse::<Template>().is_err());
+    }
+}
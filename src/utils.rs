@@ -19,6 +19,138 @@ pub fn split_at_last_segments(path: &str, n: usize) -> (Option<String>, String)
     (Some(segments.join("")), last.join(""))
 }
 
+/// How files are grouped in group mode.
+#[derive(Debug, Clone, Copy)]
+pub enum GroupKey<'a> {
+    /// The last N segments of the path.
+    LastSegments(usize),
+    /// The capture groups of a regex, or the whole match if it has none.
+    Regex(&'a regex::Regex),
+    /// The file name without its extension.
+    BasenameStem,
+}
+
+impl GroupKey<'_> {
+    /// Returns the key `path` is grouped by, or `None` if it doesn't have one.
+    pub fn key(self, path: &str) -> Option<String> {
+        match self {
+            GroupKey::LastSegments(n) => Some(split_at_last_segments(path, n).1),
+            GroupKey::Regex(regex) => {
+                let captures = regex.captures(path)?;
+                if captures.len() == 1 {
+                    return Some(captures[0].to_string());
+                }
+                Some(
+                    captures
+                        .iter()
+                        .skip(1)
+                        .flatten()
+                        .map(|m| m.as_str())
+                        .collect::<Vec<_>>()
+                        .join("/"),
+                )
+            }
+            GroupKey::BasenameStem => {
+                let stem = std::path::Path::new(path).file_stem()?;
+                Some(stem.to_string_lossy().into_owned())
+            }
+        }
+    }
+
+    /// Splits `path` into the part shown dimmed and the part relevant to the key.
+    pub fn split(self, path: &str) -> (Option<String>, String) {
+        match self {
+            GroupKey::LastSegments(n) => split_at_last_segments(path, n),
+            GroupKey::Regex(regex) => match regex.find(path) {
+                Some(m) if m.start() > 0 => (
+                    Some(path[..m.start()].to_string()),
+                    path[m.start()..].to_string(),
+                ),
+                _ => (None, path.to_string()),
+            },
+            GroupKey::BasenameStem => split_at_last_segments(path, 1),
+        }
+    }
+}
+
+/// Expands directories in `paths` into the regular files below them, in sorted order.
+/// Other paths (including "-") are kept as is. Symlinks inside directories are not followed.
+pub fn expand_paths(paths: &[String]) -> anyhow::Result<Vec<String>> {
+    let mut files = vec![];
+    for path in paths {
+        if path != "-" && std::path::Path::new(path).is_dir() {
+            walk_dir(std::path::Path::new(path), &mut files)?;
+        } else {
+            files.push(path.clone());
+        }
+    }
+
+    Ok(files)
+}
+
+fn walk_dir(dir: &std::path::Path, files: &mut Vec<String>) -> anyhow::Result<()> {
+    let mut entries = fs_err::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            walk_dir(&entry.path(), files)?;
+        } else if file_type.is_file() {
+            files.push(entry.path().to_string_lossy().into_owned());
+        }
+    }
+
+    Ok(())
+}
+
+/// Decodes manifest contents as UTF-8 or UTF-16, with or without a byte order mark, and converts
+/// CRLF line endings to LF.
+pub fn decode_text(bytes: Vec<u8>) -> anyhow::Result<String> {
+    let utf16 = |bytes: &[u8], from_bytes: fn([u8; 2]) -> u16| {
+        let units = bytes
+            .chunks(2)
+            .map(|pair| from_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+            .collect::<Vec<_>>();
+        String::from_utf16(&units).map_err(|_| anyhow::anyhow!("invalid UTF-16"))
+    };
+
+    let text = match bytes.as_slice() {
+        [0xef, 0xbb, 0xbf, rest @ ..] => String::from_utf8(rest.to_vec())?,
+        [0xff, 0xfe, rest @ ..] => utf16(rest, u16::from_le_bytes)?,
+        [0xfe, 0xff, rest @ ..] => utf16(rest, u16::from_be_bytes)?,
+        // UTF-16 without a BOM, guessed from the NUL half of an ASCII first character.
+        [first, 0, ..] if *first != 0 && bytes.len() % 2 == 0 => utf16(&bytes, u16::from_le_bytes)?,
+        [0, second, ..] if *second != 0 && bytes.len() % 2 == 0 => {
+            utf16(&bytes, u16::from_be_bytes)?
+        }
+        _ => String::from_utf8(bytes)?,
+    };
+
+    Ok(text.replace("\r\n", "\n"))
+}
+
+/// Returns the checksum shared by the most copies and how many copies share it, or `None` if
+/// there is a tie for the most copies. Failed reads (`None`) do not vote.
+pub fn find_consensus(checksums: &[Option<Vec<u8>>]) -> Option<(&[u8], usize)> {
+    let mut votes: Vec<(&[u8], usize)> = vec![];
+    for checksum in checksums.iter().flatten() {
+        match votes
+            .iter_mut()
+            .find(|(digest, _)| *digest == checksum.as_slice())
+        {
+            Some((_, count)) => *count += 1,
+            None => votes.push((checksum, 1)),
+        }
+    }
+    votes.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    match votes.as_slice() {
+        [] => None,
+        [(_, first), (_, second), ..] if first == second => None,
+        [top, ..] => Some(*top),
+    }
+}
+
 pub fn colorize_checksum(checksum_display: &str, checksum: &[u8], dim: bool) -> String {
     let (start, end) = checksum_to_gradient_colors(checksum, dim);
     let chars: Vec<char> = checksum_display.chars().collect();
@@ -89,6 +221,73 @@ mod tests {
         assert_eq!(tail, "x/y/z");
     }
 
+    #[test]
+    fn test_decode_text() {
+        let utf16 = |text: &str, bom: &[u8], to_bytes: fn(u16) -> [u8; 2]| {
+            let mut bytes = bom.to_vec();
+            bytes.extend(text.encode_utf16().flat_map(to_bytes));
+            bytes
+        };
+        let text = "abcd  caf\u{e9}.txt\r\n";
+        let expected = "abcd  caf\u{e9}.txt\n";
+
+        assert_eq!(decode_text(text.as_bytes().to_vec()).unwrap(), expected);
+        assert_eq!(
+            decode_text([b"\xef\xbb\xbf".as_slice(), text.as_bytes()].concat()).unwrap(),
+            expected
+        );
+        assert_eq!(
+            decode_text(utf16(text, &[0xff, 0xfe], u16::to_le_bytes)).unwrap(),
+            expected
+        );
+        assert_eq!(
+            decode_text(utf16(text, &[0xfe, 0xff], u16::to_be_bytes)).unwrap(),
+            expected
+        );
+        assert_eq!(
+            decode_text(utf16(text, &[], u16::to_le_bytes)).unwrap(),
+            expected
+        );
+        assert_eq!(
+            decode_text(b"abcd  file\n".to_vec()).unwrap(),
+            "abcd  file\n"
+        );
+    }
+
+    #[test]
+    fn test_find_consensus() {
+        let a = Some(vec![0xaa]);
+        let b = Some(vec![0xbb]);
+        assert_eq!(
+            find_consensus(&[a.clone(), b.clone(), a.clone(), None, a.clone()]),
+            Some((&[0xaa][..], 3))
+        );
+        assert_eq!(find_consensus(&[a.clone(), b.clone()]), None);
+        assert_eq!(find_consensus(&[None, b.clone()]), Some((&[0xbb][..], 1)));
+        assert_eq!(find_consensus(&[None]), None);
+    }
+
+    #[test]
+    fn test_group_key() {
+        let regex = regex::Regex::new(r"build-(\w+)-\d+/").unwrap();
+        let key = GroupKey::Regex(&regex);
+        assert_eq!(
+            key.key("out/build-linux-20240101/app.bin"),
+            Some("linux".to_string())
+        );
+        assert_eq!(key.key("out/app.bin"), None);
+        assert_eq!(
+            key.split("out/build-linux-20240101/app.bin"),
+            (
+                Some("out/".to_string()),
+                "build-linux-20240101/app.bin".to_string()
+            )
+        );
+
+        let key = GroupKey::BasenameStem;
+        assert_eq!(key.key("a/b/app.tar.gz"), Some("app.tar".to_string()));
+    }
+
     #[test]
     fn test_extract_last_segments_exceed() {
         let path = "/a/b/c/d/e/f/g/h/i/j/k/l/m/n/o/p/q/r/s/t/u/v/w/x/y/z";
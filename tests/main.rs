@@ -1,5 +1,26 @@
 use assert_cmd::cargo_bin;
 
+/// Copies `demo/<tree>/{01,02,03}.txt` for each of `trees` into a freshly
+/// cleared `<name>` directory under the test temp dir, and returns its path.
+fn demo_copy(name: &str, trees: &[&str]) -> anyhow::Result<std::path::PathBuf> {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir)?;
+    for tree in trees {
+        std::fs::create_dir_all(dir.join(tree))?;
+        for file in ["01.txt", "02.txt", "03.txt"] {
+            std::fs::copy(
+                std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .join("demo")
+                    .join(tree)
+                    .join(file),
+                dir.join(tree).join(file),
+            )?;
+        }
+    }
+    Ok(dir)
+}
+
 #[rstest::rstest]
 #[test]
 #[case(&[])]
@@ -74,3 +95,1132 @@ fn test_check(#[case] args: &[&str]) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_check_gnu_algorithm() -> anyhow::Result<()> {
+    // GNU lines don't name their algorithm, and are read as SHA-256 outside group mode.
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["-c", "-a", "md5"])
+        .write_stdin(
+            "1894a19c85ba153acbf743ac4e43fc004c891604b26f8c69e1e83ea2afc7c48f  demo/foo/01.txt\n",
+        )
+        .unwrap();
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stdout)?,
+        "demo/foo/01.txt: OK\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_check_streamed() -> anyhow::Result<()> {
+    use std::io::{BufRead, Write};
+
+    // Lines of plain manifests are checked before the rest of stdin is written.
+    let mut child = std::process::Command::new(cargo_bin!())
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["-c", "--strict"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = std::io::BufReader::new(child.stdout.take().unwrap());
+    stdin.write_all(
+        b"1894a19c85ba153acbf743ac4e43fc004c891604b26f8c69e1e83ea2afc7c48f  demo/foo/01.txt\n",
+    )?;
+    let mut line = String::new();
+    stdout.read_line(&mut line)?;
+    assert_eq!(line, "demo/foo/01.txt: OK\n");
+    drop(stdin);
+    assert!(child.wait()?.success());
+
+    Ok(())
+}
+
+#[test]
+fn test_check_xxh3() -> anyhow::Result<()> {
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["-a", "xxh3", "--tag", "demo/foo/01.txt", "demo/foo/02.txt"])
+        .unwrap();
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["-c", "--strict"])
+        .write_stdin(ccsum_out.stdout)
+        .unwrap();
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stdout)?,
+        "demo/foo/01.txt: OK\ndemo/foo/02.txt: OK\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_duplicates() -> anyhow::Result<()> {
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["--duplicates", "demo"])
+        .unwrap();
+
+    let lines = std::str::from_utf8(&ccsum_out.stdout)?
+        .lines()
+        .map(|line| line.split_once("  ").map(|(_, file)| file))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        [
+            Some("demo/bar/02.txt"),
+            Some("demo/foo/02.txt"),
+            None,
+            Some("demo/bar/03.txt"),
+            Some("demo/foo/03.txt"),
+        ]
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_duplicates_link() -> anyhow::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = demo_copy("duplicates_link", &["foo", "bar"])?;
+    let inode = |path: &str| Ok::<_, anyhow::Error>(std::fs::metadata(dir.join(path))?.ino());
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["--duplicates", "--link", "hard", "bar", "foo"])
+        .unwrap();
+    let stderr = std::str::from_utf8(&ccsum_out.stderr)?;
+    assert!(stderr.contains("foo/02.txt: would be replaced with a hardlink to bar/02.txt"));
+    assert!(stderr.contains("dry run, nothing was changed"));
+    assert_ne!(inode("foo/02.txt")?, inode("bar/02.txt")?);
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["--duplicates", "--link", "hard", "--apply", "bar", "foo"])
+        .unwrap();
+    let stderr = std::str::from_utf8(&ccsum_out.stderr)?;
+    assert!(stderr.contains("foo/03.txt: replaced with a hardlink to bar/03.txt"));
+    assert_eq!(inode("foo/02.txt")?, inode("bar/02.txt")?);
+    assert_eq!(inode("foo/03.txt")?, inode("bar/03.txt")?);
+    assert_ne!(inode("foo/01.txt")?, inode("bar/01.txt")?);
+    assert_eq!(
+        std::fs::read(dir.join("foo/02.txt"))?,
+        std::fs::read(dir.join("bar/02.txt"))?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_compare() -> anyhow::Result<()> {
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["--compare", "demo/foo", "demo/bar"])
+        .output()?;
+    assert!(!ccsum_out.status.success());
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stderr)?.lines().last(),
+        Some("2 identical, 1 differing, 0 missing, 0 extra, 0 unreadable")
+    );
+
+    assert!(
+        assert_cmd::Command::new(cargo_bin!())
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .args(["--compare", "demo/foo", "demo/foo"])
+            .unwrap()
+            .status
+            .success()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_group_from_manifests() -> anyhow::Result<()> {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("group_from_manifests");
+    std::fs::create_dir_all(&dir)?;
+    for tree in ["foo", "bar"] {
+        let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .args(["-a", "sha256"])
+            .args(["01.txt", "02.txt", "03.txt"].map(|name| format!("demo/{tree}/{name}")))
+            .unwrap();
+        std::fs::write(dir.join(format!("{tree}.sha256")), ccsum_out.stdout)?;
+    }
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["-G", "--from-manifests", "foo.sha256", "bar.sha256"])
+        .output()?;
+    assert!(!ccsum_out.status.success());
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stderr)?,
+        "01.txt: no consensus among 2 copies\n"
+    );
+    // Redirected output is a valid manifest, without the names of the source manifests.
+    assert!(std::str::from_utf8(&ccsum_out.stdout)?.starts_with(
+        "1894a19c85ba153acbf743ac4e43fc004c891604b26f8c69e1e83ea2afc7c48f  demo/foo/01.txt\n"
+    ));
+
+    assert!(
+        assert_cmd::Command::new(cargo_bin!())
+            .current_dir(&dir)
+            .args(["-G", "--from-manifests", "foo.sha256", "foo.sha256"])
+            .unwrap()
+            .status
+            .success()
+    );
+
+    Ok(())
+}
+
+#[rstest::rstest]
+#[case("json")]
+#[case("ndjson")]
+fn test_json_roundtrip(#[case] output_format: &str) -> anyhow::Result<()> {
+    let files = glob::glob(concat!(env!("CARGO_MANIFEST_DIR"), "/src/*.rs"))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .args(["--output-format", output_format])
+        .args(&files)
+        .unwrap();
+
+    let check_out = assert_cmd::Command::new(cargo_bin!())
+        .args(["-c", "--output-format", "ndjson"])
+        .write_stdin(ccsum_out.stdout)
+        .unwrap();
+    let statuses = std::str::from_utf8(&check_out.stdout)?
+        .lines()
+        .map(|line| Ok(serde_json::from_str::<serde_json::Value>(line)?["status"].clone()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    assert_eq!(statuses, vec![serde_json::json!("ok"); files.len()]);
+
+    Ok(())
+}
+
+#[test]
+fn test_report_stdout() -> anyhow::Result<()> {
+    let manifest = "1894a19c85ba153acbf743ac4e43fc004c891604b26f8c69e1e83ea2afc7c48f  demo/foo/01.txt\n\
+        0000000000000000000000000000000000000000000000000000000000000000  demo/foo/02.txt\n";
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["-c", "--report", "tap"])
+        .write_stdin(manifest)
+        .output()?;
+    assert!(!ccsum_out.status.success());
+    let tap = std::str::from_utf8(&ccsum_out.stdout)?;
+    let mut lines = tap.lines();
+    assert_eq!(lines.next(), Some("TAP version 13"));
+    assert_eq!(lines.next(), Some("1..2"));
+    let results = lines
+        .filter(|line| !line.starts_with("  "))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        results,
+        ["ok 1 - demo/foo/01.txt", "not ok 2 - demo/foo/02.txt"]
+    );
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["-c", "--report", "junit"])
+        .write_stdin(manifest)
+        .output()?;
+    let junit = std::str::from_utf8(&ccsum_out.stdout)?;
+    assert!(junit.starts_with("<?xml "));
+    assert!(junit.ends_with("</testsuites>\n"));
+
+    assert!(
+        !assert_cmd::Command::new(cargo_bin!())
+            .args(["-c", "--report", "tap", "--output-format", "json"])
+            .write_stdin(manifest)
+            .output()?
+            .status
+            .success()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_printf() -> anyhow::Result<()> {
+    let files = glob::glob(concat!(env!("CARGO_MANIFEST_DIR"), "/src/*.rs"))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .args(["-a", "sha256", "--printf", "%h  %f\\n"])
+        .args(&files)
+        .unwrap();
+    let sha256_out = assert_cmd::Command::new("sha256sum").args(&files).unwrap();
+
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stdout)?,
+        std::str::from_utf8(&sha256_out.stdout)?
+    );
+
+    Ok(())
+}
+
+#[rstest::rstest]
+#[case("hex-upper")]
+#[case("base32")]
+#[case("base32-no-pad")]
+#[case("base32-crockford")]
+#[case("base58")]
+#[case("z85")]
+#[case("oci")]
+#[case("sri")]
+#[case("nix32")]
+#[case("multihash")]
+#[case("cid")]
+fn test_check_format(#[case] format: &str) -> anyhow::Result<()> {
+    let files = glob::glob(concat!(env!("CARGO_MANIFEST_DIR"), "/src/*.rs"))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .args(["-a", "sha512", "--tag", "--format", format])
+        .args(&files)
+        .unwrap();
+    assert!(
+        assert_cmd::Command::new(cargo_bin!())
+            .args(["-c", "--strict", "--format", format])
+            .write_stdin(ccsum_out.stdout)
+            .unwrap()
+            .status
+            .success()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_check_format_detected() -> anyhow::Result<()> {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("check_format_detected");
+    std::fs::create_dir_all(&dir)?;
+    // The base58 SHA-256 digest of `04` is also valid unpadded base64.
+    std::fs::write(dir.join("04.txt"), "04\n")?;
+    std::fs::write(dir.join("05.txt"), "05\n")?;
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["--format", "base58", "04.txt", "05.txt"])
+        .unwrap();
+    assert!(
+        std::str::from_utf8(&ccsum_out.stdout)?
+            .starts_with("Ec1j8cbR1z5B65UcLVRaegdLkNERTeUpBt6irTVV1rk  04.txt\n")
+    );
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["-c", "--strict"])
+        .write_stdin(ccsum_out.stdout)
+        .unwrap();
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stdout)?,
+        "04.txt: OK\n05.txt: OK\n"
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_check_escaped() -> anyhow::Result<()> {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("check_escaped");
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join("new\nline.txt"), "01\n")?;
+    std::fs::write(dir.join(r"C:\temp\new.txt"), "01\n")?;
+    std::fs::write(dir.join("Bob's \"file\".txt"), "01\n")?;
+    let digest = "43fd56f56bb9bb18bc9c33966325732b2d7e58bfe2504a2c5c164b071c1b8653";
+
+    // Like sha256sum, only backslashes and line breaks are escaped, and escaped names are
+    // marked with a leading backslash.
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["new\nline.txt", r"C:\temp\new.txt", "Bob's \"file\".txt"])
+        .unwrap();
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stdout)?,
+        format!(
+            "\\{digest}  new\\nline.txt\n\\{digest}  C:\\\\temp\\\\new.txt\n{digest}  Bob's \"file\".txt\n"
+        )
+    );
+    for command in [cargo_bin!(), "sha256sum".as_ref()] {
+        assert!(
+            assert_cmd::Command::new(command)
+                .current_dir(&dir)
+                .args(["-c", "--strict"])
+                .write_stdin(ccsum_out.stdout.clone())
+                .unwrap()
+                .status
+                .success()
+        );
+    }
+
+    // Without the marker, backslashes are kept as they are.
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["-c", "--strict"])
+        .write_stdin(format!("{digest}  C:\\temp\\new.txt\n"))
+        .unwrap();
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stdout)?,
+        "C:\\temp\\new.txt: OK\n"
+    );
+
+    // Older versions escaped quotes without the marker.
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["-c", "--strict"])
+        .write_stdin(format!("{digest}  Bob\\'s \\\"file\\\".txt\n"))
+        .unwrap();
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stdout)?,
+        "Bob's \"file\".txt: OK\n"
+    );
+
+    // A marked line with an invalid escape is an invalid line.
+    assert!(
+        !assert_cmd::Command::new(cargo_bin!())
+            .current_dir(&dir)
+            .args(["-c", "--strict"])
+            .write_stdin(format!("\\{digest}  C:\\temp\\new.txt\n"))
+            .output()?
+            .status
+            .success()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_check_invalid_digest() -> anyhow::Result<()> {
+    let manifest = "00  demo/foo/01.txt\n\
+        1894a19c85ba153acbf743ac4e43fc004c891604b26f8c69e1e83ea2afc7c48f  demo/foo/01.txt\n";
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["-c", "--warn"])
+        .write_stdin(manifest)
+        .output()?;
+    assert!(ccsum_out.status.success());
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stderr)?.lines().next(),
+        Some("-: invalid line")
+    );
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["-c", "--strict"])
+        .write_stdin(manifest)
+        .output()?;
+    assert!(!ccsum_out.status.success());
+
+    Ok(())
+}
+
+#[test]
+fn test_raw() -> anyhow::Result<()> {
+    let file = concat!(env!("CARGO_MANIFEST_DIR"), "/src/main.rs");
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .args(["-a", "sha256", "--format", "raw", file])
+        .unwrap();
+    let sha256_out = assert_cmd::Command::new("sha256sum").arg(file).unwrap();
+    let sha256_out = std::str::from_utf8(&sha256_out.stdout)?;
+
+    assert_eq!(
+        hex::encode(&ccsum_out.stdout),
+        sha256_out.split_once("  ").unwrap().0
+    );
+
+    for args in [
+        &[file][..],
+        &["--tag"],
+        &["--format-style", "bsd"],
+        &["--format-style", "mtree"],
+    ] {
+        assert!(
+            !assert_cmd::Command::new(cargo_bin!())
+                .args(["--format", "raw", file])
+                .args(args)
+                .output()?
+                .status
+                .success()
+        );
+    }
+
+    Ok(())
+}
+
+#[rstest::rstest]
+#[case("gnu", "sha256")]
+#[case("bare", "md5")]
+fn test_sidecar(#[case] style: &str, #[case] algorithm: &str) -> anyhow::Result<()> {
+    let dir = demo_copy(&format!("sidecar/{style}"), &["foo"])?.join("foo");
+
+    assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["-a", algorithm, &format!("--sidecar={style}"), "."])
+        .unwrap();
+    assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["--check-sidecars", "."])
+        .unwrap();
+
+    std::fs::write(dir.join("02.txt"), "changed")?;
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["--check-sidecars", "."])
+        .output()?;
+    assert!(!ccsum_out.status.success());
+    assert!(std::str::from_utf8(&ccsum_out.stderr)?.starts_with("./02.txt: checksum mismatch"));
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stdout)?,
+        "./01.txt: OK\n./03.txt: OK\n"
+    );
+
+    // Sidecars of any algorithm are skipped when run again.
+    assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["-a", "sha1", &format!("--sidecar={style}"), "."])
+        .unwrap();
+    assert!(!dir.join(format!("01.txt.{algorithm}.sha1")).exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_sfv() -> anyhow::Result<()> {
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args([
+            "--format-style",
+            "sfv",
+            "demo/foo/01.txt",
+            "demo/foo/02.txt",
+        ])
+        .unwrap();
+    let sfv = std::str::from_utf8(&ccsum_out.stdout)?;
+    assert_eq!(sfv, "demo/foo/01.txt B739E0D5\ndemo/foo/02.txt 04152E84\n");
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["-c", "--strict"])
+        .write_stdin(format!(
+            "; generated by hand\n{sfv}demo/bar/01.txt 00000000\n"
+        ))
+        .output()?;
+    assert!(!ccsum_out.status.success());
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stdout)?,
+        "demo/foo/01.txt: OK\ndemo/foo/02.txt: OK\n"
+    );
+
+    // The algorithm and format of SFV are fixed.
+    for args in [["-a", "md5"], ["--format", "base64"]] {
+        assert!(
+            !assert_cmd::Command::new(cargo_bin!())
+                .current_dir(env!("CARGO_MANIFEST_DIR"))
+                .args(["--format-style", "sfv", "demo/foo/01.txt"])
+                .args(args)
+                .output()?
+                .status
+                .success()
+        );
+    }
+
+    Ok(())
+}
+
+#[rstest::rstest]
+#[case(&[])]
+#[case(&["--format-style", "sfv"])]
+fn test_check_bracketed_name(#[case] args: &[&str]) -> anyhow::Result<()> {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("check_bracketed_name");
+    std::fs::create_dir_all(&dir)?;
+    // Manifests starting with `[` or `{` aren't necessarily JSON.
+    for name in ["[Group] Show 01.mkv", "{Group} Show 02.mkv"] {
+        std::fs::write(dir.join(name), name)?;
+        let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+            .current_dir(&dir)
+            .args(args)
+            .arg(name)
+            .unwrap();
+        let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+            .current_dir(&dir)
+            .args(["-c", "--strict"])
+            .write_stdin(ccsum_out.stdout)
+            .unwrap();
+        assert_eq!(
+            std::str::from_utf8(&ccsum_out.stdout)?,
+            format!("{name}: OK\n")
+        );
+    }
+
+    Ok(())
+}
+
+#[rstest::rstest]
+#[case("sha1")]
+#[case("sha512")]
+#[case("xxh32")]
+#[case("xxh64")]
+#[case("xxh3")]
+#[case("crc32")]
+fn test_hashdeep_columns(#[case] algorithm: &str) -> anyhow::Result<()> {
+    let digest = |algorithm: &str| -> anyhow::Result<String> {
+        let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .args(["-a", algorithm, "demo/foo/01.txt"])
+            .unwrap();
+        Ok(std::str::from_utf8(&ccsum_out.stdout)?
+            .split_once("  ")
+            .unwrap()
+            .0
+            .to_string())
+    };
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args([
+            "--format-style",
+            "hashdeep",
+            "-a",
+            algorithm,
+            "demo/foo/01.txt",
+        ])
+        .unwrap();
+    let line = std::str::from_utf8(&ccsum_out.stdout)?
+        .lines()
+        .last()
+        .unwrap()
+        .to_string();
+    assert_eq!(
+        line,
+        format!(
+            "12,{},{},demo/foo/01.txt",
+            digest("md5")?,
+            digest(algorithm)?
+        )
+    );
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .args(["--format-style", "hashdeep", "-"])
+        .output()?;
+    assert!(!ccsum_out.status.success());
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stderr)?,
+        "-: stdin is not supported here\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_hashdeep_audit() -> anyhow::Result<()> {
+    let dir = demo_copy("hashdeep_audit", &["foo", "bar"])?;
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["--format-style", "hashdeep", "foo", "bar"])
+        .unwrap();
+    std::fs::write(dir.join("known.txt"), &ccsum_out.stdout)?;
+    assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["-c", "known.txt"])
+        .unwrap();
+
+    std::fs::rename(dir.join("foo/01.txt"), dir.join("foo/04.txt"))?;
+    std::fs::write(dir.join("foo/05.txt"), "new")?;
+    std::fs::write(dir.join("bar/02.txt"), "changed")?;
+    std::fs::remove_file(dir.join("bar/03.txt"))?;
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["--audit", "known.txt", "foo", "bar"])
+        .output()?;
+    assert!(!ccsum_out.status.success());
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stdout)?,
+        "foo/02.txt: matched\nfoo/03.txt: matched\nbar/01.txt: matched\n"
+    );
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stderr)?,
+        "foo/04.txt: moved from foo/01.txt\n\
+         foo/05.txt: new\n\
+         bar/02.txt: changed\n\
+         bar/03.txt: missing\n\
+         3 matched, 1 moved, 1 new, 1 changed, 1 missing\n"
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_mtree() -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = demo_copy("mtree", &["foo"])?;
+    for name in ["01.txt", "02.txt", "03.txt"] {
+        std::fs::set_permissions(
+            dir.join("foo").join(name),
+            std::fs::Permissions::from_mode(0o644),
+        )?;
+    }
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["--format-style", "mtree", "foo"])
+        .unwrap();
+    let spec = std::str::from_utf8(&ccsum_out.stdout)?;
+    assert!(spec.starts_with("#mtree\n./foo type=dir mode="));
+    assert!(spec.contains("./foo/01.txt type=file mode=0644 uid="));
+    assert!(spec.contains(
+        " size=12 sha256digest=1894a19c85ba153acbf743ac4e43fc004c891604b26f8c69e1e83ea2afc7c48f\n"
+    ));
+    std::fs::write(dir.join("spec"), spec)?;
+    assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["-c", "spec"])
+        .unwrap();
+
+    std::fs::set_permissions(
+        dir.join("foo/01.txt"),
+        std::fs::Permissions::from_mode(0o600),
+    )?;
+    std::fs::write(dir.join("foo/02.txt"), "changed but same size")?;
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["-c", "spec"])
+        .output()?;
+    assert!(!ccsum_out.status.success());
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stdout)?,
+        "./foo: OK\n./foo/01.txt: OK\n./foo/03.txt: OK\n"
+    );
+    let stderr = std::str::from_utf8(&ccsum_out.stderr)?;
+    assert!(stderr.starts_with(
+        "./foo/01.txt: metadata mismatch: mode expected 0644, got 0600\n\
+         ./foo/02.txt: metadata mismatch: size expected 19, got 21\n\
+         ./foo/02.txt: checksum mismatch: "
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_check_base_directory() -> anyhow::Result<()> {
+    let dir = demo_copy("check_base_directory", &["foo"])?;
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("demo/foo"))
+        .args(["01.txt", "02.txt"])
+        .unwrap();
+    std::fs::write(dir.join("foo/SHA256SUMS"), &ccsum_out.stdout)?;
+
+    assert!(
+        !assert_cmd::Command::new(cargo_bin!())
+            .current_dir(&dir)
+            .args(["-c", "foo/SHA256SUMS"])
+            .output()?
+            .status
+            .success()
+    );
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["-c", "--relative-to-manifest", "foo/SHA256SUMS"])
+        .unwrap();
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stdout)?,
+        "01.txt: OK\n02.txt: OK\n"
+    );
+
+    assert_cmd::Command::new(cargo_bin!())
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["-c", "--directory", "demo/foo"])
+        .arg(dir.join("foo/SHA256SUMS"))
+        .unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn test_check_rewrite_paths() -> anyhow::Result<()> {
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["demo/foo/01.txt", "demo/bar/02.txt"])
+        .unwrap();
+    let manifest = std::str::from_utf8(&ccsum_out.stdout)?
+        .replace("demo/foo/", "C:\\Release\\FOO\\")
+        .replace("demo/bar/", "/build/out/bar/");
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("demo"))
+        .args(["-c", "--convert-separators", "--ignore-case"])
+        .args(["--strip-prefix", "/build/out/"])
+        .args(["--replace-prefix", "c:\\release\\=./"])
+        .write_stdin(manifest)
+        .unwrap();
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stdout)?,
+        "C:\\Release\\FOO\\01.txt: OK\n/build/out/bar/02.txt: OK\n"
+    );
+
+    Ok(())
+}
+
+#[rstest::rstest]
+#[case("nfd")]
+#[case("auto")]
+fn test_check_normalize_names(#[case] form: &str) -> anyhow::Result<()> {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("normalize_names");
+    std::fs::create_dir_all(&dir)?;
+    // The file is stored in NFD, as on macOS, and listed in NFC.
+    std::fs::write(dir.join("cafe\u{301}.txt"), "hello world\n")?;
+    let manifest =
+        "a948904f2f0f479b8f8197694b30184b0d2ed1c1cd2a1ec0fb85d299a192a447  caf\u{e9}.txt\n";
+
+    assert!(
+        !assert_cmd::Command::new(cargo_bin!())
+            .current_dir(&dir)
+            .arg("-c")
+            .write_stdin(manifest)
+            .output()?
+            .status
+            .success()
+    );
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["-c", "--normalize-names", form])
+        .write_stdin(manifest)
+        .unwrap();
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stdout)?,
+        "caf\u{e9}.txt: OK\n"
+    );
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stderr)?,
+        "caf\u{e9}.txt: found as NFD\n"
+    );
+
+    // The form is also in structured output.
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["-c", "--normalize-names", form, "--output-format", "ndjson"])
+        .write_stdin(manifest)
+        .unwrap();
+    assert!(std::str::from_utf8(&ccsum_out.stdout)?.contains(r#""normalization":"NFD""#));
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(&dir)
+        .args(["-c", "--normalize-names", form, "--report", "tap"])
+        .write_stdin(manifest)
+        .unwrap();
+    assert!(std::str::from_utf8(&ccsum_out.stdout)?.contains("  normalization: NFD\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_check_windows_manifest() -> anyhow::Result<()> {
+    let manifest = "\u{feff}a948904f2f0f479b8f8197694b30184b0d2ed1c1cd2a1ec0fb85d299a192a447 *demo\\bar\\01.txt\r\n\
+         1894a19c85ba153acbf743ac4e43fc004c891604b26f8c69e1e83ea2afc7c48f  demo\\foo\\01.txt\r\n";
+    let mut utf16 = vec![];
+    for unit in manifest.encode_utf16() {
+        utf16.extend(unit.to_le_bytes());
+    }
+
+    for contents in [manifest.as_bytes().to_vec(), utf16] {
+        let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .args(["-c", "--strict"])
+            .write_stdin(contents)
+            .unwrap();
+        assert_eq!(
+            std::str::from_utf8(&ccsum_out.stdout)?,
+            "demo\\bar\\01.txt: OK\ndemo\\foo\\01.txt: OK\n"
+        );
+    }
+
+    Ok(())
+}
+
+#[rstest::rstest]
+#[case::openssl(
+    "demo/foo/01.txt\ndemo/foo/02.txt\n",
+    "SHA2-256(demo/foo/01.txt)= 1894a19c85ba153acbf743ac4e43fc004c891604b26f8c69e1e83ea2afc7c48f\n\
+     MD5(demo/foo/02.txt)= 4df98492914b720dd5489c75f4e0d192\n"
+)]
+#[case::certutil(
+    "demo/foo/01.txt\ndemo/foo/02.txt\n",
+    "SHA256 hash of demo/foo/01.txt:\n\
+     18 94 a1 9c 85 ba 15 3a cb f7 43 ac 4e 43 fc 00 4c 89 16 04 b2 6f 8c 69 e1 e8 3e a2 af c7 c4 8f\n\
+     CertUtil: -hashfile command completed successfully.\n\
+     MD5 hash of demo/foo/02.txt:\n\
+     4df98492914b720dd5489c75f4e0d192\n"
+)]
+#[case::powershell_table(
+    "demo\\foo\\01.txt\ndemo\\foo\\02.txt\n",
+    "\nAlgorithm       Hash                                                                   Path\n\
+     ---------       ----                                                                   ----\n\
+     SHA256          1894A19C85BA153ACBF743AC4E43FC004C891604B26F8C69E1E83EA2AFC7C48F       demo\\foo\\01.txt\n\
+     MD5             4DF98492914B720DD5489C75F4E0D192                                       demo\\foo\\02.txt\n\n"
+)]
+#[case::powershell_csv(
+    "demo/foo/01.txt\ndemo/foo/02.txt\n",
+    "#TYPE Microsoft.PowerShell.Commands.FileHashInfo\n\
+     \"Algorithm\",\"Hash\",\"Path\"\n\
+     \"SHA256\",\"1894A19C85BA153ACBF743AC4E43FC004C891604B26F8C69E1E83EA2AFC7C48F\",\"demo/foo/01.txt\"\n\
+     \"MD5\",\"4DF98492914B720DD5489C75F4E0D192\",\"demo/foo/02.txt\"\n"
+)]
+fn test_check_foreign(#[case] files: &str, #[case] manifest: &str) -> anyhow::Result<()> {
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["-c", "--strict"])
+        .write_stdin(manifest)
+        .unwrap();
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stdout)?,
+        files.replace('\n', ": OK\n")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_check_loose() -> anyhow::Result<()> {
+    let notes = "# Release 1.0\n\n\
+        Download [01.txt](https://example.com/demo/foo/01.txt). SHA256 of demo/foo/01.txt: \
+        `1894a19c85ba153acbf743ac4e43fc004c891604b26f8c69e1e83ea2afc7c48f`\n\n\
+        ### demo/foo/02.txt\n\n\
+        - MD5: <code>4df98492914b720dd5489c75f4e0d192</code>\n\
+        - sha1sum: 0000000000000000000000000000000000000000\n\n\
+        Thanks to 1234567890 contributors.\n";
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["-c", "--loose"])
+        .write_stdin(notes)
+        .output()?;
+    assert!(!ccsum_out.status.success());
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stdout)?,
+        "demo/foo/01.txt: OK\ndemo/foo/02.txt: OK\n"
+    );
+    assert!(std::str::from_utf8(&ccsum_out.stderr)?.contains("demo/foo/02.txt: checksum mismatch"));
+
+    Ok(())
+}
+
+#[test]
+fn test_check_pgp_signed() -> anyhow::Result<()> {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("check_pgp_signed");
+    std::fs::create_dir_all(&dir)?;
+    let signature =
+        "-----BEGIN PGP SIGNATURE-----\n\niQEzBAEBCAAdFiEE\n=abcd\n-----END PGP SIGNATURE-----\n";
+    let manifest = format!(
+        "-----BEGIN PGP SIGNED MESSAGE-----\nHash: SHA256\n\n\
+         1894a19c85ba153acbf743ac4e43fc004c891604b26f8c69e1e83ea2afc7c48f  demo/foo/01.txt\n\
+         - --- release 1.0 ---\n\
+         {signature}\
+         0000000000000000000000000000000000000000000000000000000000000000  demo/foo/03.txt\n"
+    );
+
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .arg("-c")
+        .arg("--extract-signature")
+        .arg(dir.join("SHA256SUMS.sig"))
+        .arg("--extract-signed-body")
+        .arg(dir.join("SHA256SUMS"))
+        .write_stdin(manifest)
+        .unwrap();
+    // The mismatching line after the signature isn't checked.
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stdout)?,
+        "demo/foo/01.txt: OK\n"
+    );
+    assert!(
+        std::str::from_utf8(&ccsum_out.stderr)?
+            .contains("ignoring 1 lines outside the signed message")
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.join("SHA256SUMS.sig"))?,
+        signature
+    );
+    assert!(std::fs::read_to_string(dir.join("SHA256SUMS"))?.ends_with("\n--- release 1.0 ---"));
+
+    Ok(())
+}
+
+#[rstest::rstest]
+#[case::signify(
+    "sig",
+    "untrusted comment: signify public key\nRWQBAgMEBQYHCIqI4910CfGV/VLbLTy6XXLKZwm/HZQSG/N0iAG0D29c\n",
+    "untrusted comment: signify secret key\nRWRCSwAAAAAAAAAAAAAAAAAAAAAAAAAAKL0cGdCWlyMBAgMEBQYHCAEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBiojj3XQJ8ZX9UtstPLpdcspnCb8dlBIb83SIAbQPb1w=\n"
+)]
+#[case::minisign(
+    "minisig",
+    "untrusted comment: minisign public key\nRWQREhMUFRYXGIE5dw6ofRdfVqNUZsNMfszLjYqRtO43ol32D1uPybOU\n",
+    "untrusted comment: minisign secret key\nRWQAAEIyAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAERITFBUWFxgCAgICAgICAgICAgICAgICAgICAgICAgICAgICAgICAoE5dw6ofRdfVqNUZsNMfszLjYqRtO43ol32D1uPybOUPcG9l0tkT4aR+XmI2Edmx1FJDDn/bqEyN0S0b5rdi6A=\n"
+)]
+fn test_check_signed_manifest(
+    #[case] extension: &str,
+    #[case] public_key: &str,
+    #[case] secret_key: &str,
+) -> anyhow::Result<()> {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join(format!("check_signed_manifest_{extension}"));
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join("key.pub"), public_key)?;
+    std::fs::write(dir.join("key.sec"), secret_key)?;
+    let manifest = dir.join("SHA256SUMS");
+    std::fs::write(
+        &manifest,
+        "1894a19c85ba153acbf743ac4e43fc004c891604b26f8c69e1e83ea2afc7c48f  demo/foo/01.txt\n",
+    )?;
+
+    assert_cmd::Command::new(cargo_bin!())
+        .arg("--sign")
+        .arg(dir.join("key.sec"))
+        .arg(&manifest)
+        .unwrap();
+    assert!(dir.join(format!("SHA256SUMS.{extension}")).exists());
+
+    let check = || {
+        assert_cmd::Command::new(cargo_bin!())
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .arg("-c")
+            .arg("--pubkey")
+            .arg(dir.join("key.pub"))
+            .arg(&manifest)
+            .output()
+    };
+    let ccsum_out = check()?;
+    assert!(ccsum_out.status.success());
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stdout)?,
+        "demo/foo/01.txt: OK\n"
+    );
+
+    std::fs::write(
+        dir.join("allowed_signers"),
+        "dev@example.com ssh-ed25519 \
+         AAAAC3NzaC1lZDI1NTE5AAAAICxhoK/lI9AQzb2eBx5b41fRWUkYsvZP9mrbbNMGP07m\n",
+    )?;
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .arg("-c")
+        .arg("--allowed-signers")
+        .arg(dir.join("allowed_signers"))
+        .args(["--identity", "dev@example.com"])
+        .arg(&manifest)
+        .output()?;
+    assert!(!ccsum_out.status.success());
+    assert!(
+        std::str::from_utf8(&ccsum_out.stderr)?
+            .contains("signature, verify it with --pubkey instead")
+    );
+
+    // No entry is checked once the manifest doesn't match its signature.
+    std::fs::write(
+        &manifest,
+        "1894a19c85ba153acbf743ac4e43fc004c891604b26f8c69e1e83ea2afc7c48f  demo/foo/01.txt\n\
+         98b3f6cd4061c7249ead674edbf0e0d617c6723d59be95167ba52a159992be07  demo/foo/02.txt\n",
+    )?;
+    let ccsum_out = check()?;
+    assert!(!ccsum_out.status.success());
+    assert_eq!(std::str::from_utf8(&ccsum_out.stdout)?, "");
+    assert!(std::str::from_utf8(&ccsum_out.stderr)?.contains("invalid signature"));
+
+    Ok(())
+}
+
+#[test]
+fn test_check_ssh_signed_manifest() -> anyhow::Result<()> {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("check_ssh_signed_manifest");
+    std::fs::create_dir_all(&dir)?;
+    // A throwaway key from a fixed seed.
+    let key = ssh_key::PrivateKey::new(
+        ssh_key::private::KeypairData::Ed25519(ssh_key::private::Ed25519Keypair::from_seed(
+            &[7; 32],
+        )),
+        "dev@example.com",
+    )?;
+    std::fs::write(
+        dir.join("id_ed25519"),
+        key.to_openssh(ssh_key::LineEnding::LF)?,
+    )?;
+    std::fs::write(
+        dir.join("allowed_signers"),
+        format!(
+            "dev@example.com namespaces=\"file\" {}\n",
+            key.public_key().to_openssh()?
+        ),
+    )?;
+    let manifest = dir.join("SHA256SUMS");
+    std::fs::write(
+        &manifest,
+        "1894a19c85ba153acbf743ac4e43fc004c891604b26f8c69e1e83ea2afc7c48f  demo/foo/01.txt\n",
+    )?;
+
+    assert_cmd::Command::new(cargo_bin!())
+        .arg("--sign")
+        .arg(dir.join("id_ed25519"))
+        .arg(&manifest)
+        .unwrap();
+    assert!(
+        std::fs::read_to_string(dir.join("SHA256SUMS.sig"))?
+            .starts_with("-----BEGIN SSH SIGNATURE-----\n")
+    );
+
+    let check = |args: &[&str]| {
+        assert_cmd::Command::new(cargo_bin!())
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .arg("-c")
+            .arg("--allowed-signers")
+            .arg(dir.join("allowed_signers"))
+            .args(args)
+            .arg(&manifest)
+            .output()
+    };
+    let ccsum_out = check(&["--identity", "dev@example.com"])?;
+    assert!(ccsum_out.status.success());
+    assert_eq!(
+        std::str::from_utf8(&ccsum_out.stdout)?,
+        "demo/foo/01.txt: OK\n"
+    );
+
+    std::fs::write(
+        dir.join("key.pub"),
+        "untrusted comment: signify public key\nRWQBAgMEBQYHCIqI4910CfGV/VLbLTy6XXLKZwm/HZQSG/N0iAG0D29c\n",
+    )?;
+    let ccsum_out = assert_cmd::Command::new(cargo_bin!())
+        .arg("-c")
+        .arg("--pubkey")
+        .arg(dir.join("key.pub"))
+        .arg(&manifest)
+        .output()?;
+    assert!(!ccsum_out.status.success());
+    assert!(
+        std::str::from_utf8(&ccsum_out.stderr)?
+            .contains("an SSH signature, verify it with --allowed-signers instead")
+    );
+
+    for args in [
+        &["--identity", "other@example.com"][..],
+        &["--identity", "dev@example.com", "--namespace", "git"],
+        // Like `ssh-keygen -Y verify`, the signer must be named.
+        &[],
+    ] {
+        let ccsum_out = check(args)?;
+        assert!(!ccsum_out.status.success());
+        assert_eq!(std::str::from_utf8(&ccsum_out.stdout)?, "");
+    }
+
+    Ok(())
+}